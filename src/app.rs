@@ -1,27 +1,82 @@
+use crate::currency::{MinorUnits, Rates};
+use crate::expr;
+use crate::filter::Query;
+use crate::household::{Balance, Share, Transfer};
+use crate::locale::Locale;
+use crate::split::Split;
+use crate::stats::{CategoryNode, Cheapest, StoreStats, TaxRow};
+use crate::tax::TaxRates;
+use crate::worker::{Busy, Job};
 use ratatui::widgets::*;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
 use tui_textarea::TextArea;
 
+pub const DEFAULT_CATEGORY_SEPARATOR: &str = ":";
+
 pub struct App<'a> {
     pub state: AppState,
 
     // Cursor position in main table.
     pub table_state: TableState,
 
+    // Cursor position in secondary views, e.g. store statistics.
+    pub view_state: TableState,
+
     // Cursor position in history list.
     pub list_state: ListState,
 
     // Text area widget for entering Date, Category, Description.
     pub textarea: TextArea<'a>,
 
-    // Copy of DbItem being edited.
+    // Copy of DbItem being edited, or of the purchase being refunded, which
+    // only prefills the prompts as the new item has no id yet.
     pub item_template: Option<DbItem>,
 
     // New item being created.
     pub new_item: DbItem,
 
-    // Items queried from database, possibly incomplete.
+    // Receipt that new items are added to, and the sum of its items so far.
+    pub receipt: Option<DbReceipt>,
+    pub receipt_sum: i64,
+
+    // Message shown in place of the prompt in Browse mode.
+    pub message: String,
+
+    // Whether the message reports a problem.
+    pub message_is_error: bool,
+
+    // Items queried from database, possibly incomplete: a window of the
+    // items matching the filter, loaded around the cursor.  See window.rs.
     pub items: Vec<DbItem>,
 
+    // Position of the first loaded item among all items matching the
+    // filter, and how many items match.
+    pub items_offset: usize,
+    pub item_count: usize,
+
+    // Amount of all items matching the filter in the home currency, and the
+    // number of items that could not be converted.
+    pub total: i64,
+    pub unconverted: usize,
+
+    // Index in items of the first row shown in the table.
+    pub table_scroll: usize,
+
+    // Split items whose allocations are shown below them in the table.
+    pub expanded: BTreeSet<i64>,
+
+    // Long operation for the worker thread to start, and the one running.
+    pub job: Option<Job>,
+    pub busy: Option<Busy>,
+
+    // PRAGMA data_version when the items were last read, to notice changes
+    // made by other programs.
+    pub data_version: i64,
+
+    // Deleted items, queried on entering the Trash view.
+    pub trash: Vec<DbItem>,
+
     // List of previously entered values, possibly incomplete.
     pub history: Vec<String>,
 
@@ -30,6 +85,74 @@ pub struct App<'a> {
 
     // Descriptions queried from database.
     pub distinct_descriptions: Vec<String>,
+
+    // Stores queried from database.
+    pub distinct_stores: Vec<String>,
+
+    // Tags queried from database.
+    pub distinct_tags: Vec<String>,
+
+    // Members of the household queried from database.
+    pub distinct_members: Vec<String>,
+
+    // Only show items matching this filter.
+    pub filter: ItemFilter,
+
+    // Whether the search being typed is not a valid query.
+    pub search_error: bool,
+
+    // Named filters, recalled as "@name" in a filter.
+    pub saved_filters: BTreeMap<String, String>,
+
+    // Per-store statistics, computed on entering the Stores view.
+    pub store_stats: Vec<StoreStats>,
+    pub cheapest: Vec<Cheapest>,
+
+    // Category tree, computed on entering the Categories view.
+    pub category_tree: Vec<CategoryNode>,
+
+    // Categories marked in the Categories view, to be merged.
+    pub marked_categories: BTreeSet<String>,
+
+    // Balance of each member, the transfers that would settle them, and
+    // those recorded so far, most recent first, computed on entering the
+    // Balances view.
+    pub balances: Vec<Balance>,
+    pub settlement: Vec<Transfer>,
+    pub transfers: Vec<Transfer>,
+
+    // Member who paid for the selected item, while its shares are typed.
+    pub payer: String,
+
+    // Separator between levels of a category, e.g. "Food:Dairy".
+    pub category_separator: String,
+
+    // Ordering of the main table.
+    pub sort: SortOrder,
+
+    // Currency that amounts are converted to, empty if not configured.
+    pub home_currency: String,
+
+    // Exchange rates queried from database.
+    pub rates: Rates,
+
+    // Separators and currency symbol for amounts.
+    pub locale: Locale,
+
+    // Digits after the decimal point per currency.
+    pub minor_units: MinorUnits,
+
+    // Tax rates and the defaults per category.
+    pub tax_rates: TaxRates,
+
+    // Tax per rate and period, computed on entering the TaxReport view, and
+    // whether periods are years rather than months.
+    pub tax_report: Vec<TaxRow>,
+    pub tax_yearly: bool,
+
+    // Local format that dates may also be typed in, e.g. "%d.%m.%Y", or
+    // empty.
+    pub date_format: String,
 }
 
 impl App<'_> {
@@ -37,6 +160,7 @@ impl App<'_> {
         App {
             state: AppState::Browse,
             table_state: TableState::default(),
+            view_state: TableState::default(),
             list_state: ListState::default(),
             textarea: TextArea::<'a>::default(),
 
@@ -47,12 +171,64 @@ impl App<'_> {
                 category: String::new(),
                 description: String::new(),
                 price: 0,
+                currency: String::new(),
+                quantity: 1.0,
+                unit: String::new(),
+                store: String::new(),
+                receipt_id: None,
+                tags: Vec::new(),
+                deleted_at: None,
+                kind: ItemKind::Purchase,
+                original_id: None,
+                tax_rate: None,
+                splits: Vec::new(),
+                payer: String::new(),
+                shares: Vec::new(),
             },
 
+            receipt: None,
+            receipt_sum: 0,
+            message: String::new(),
+            message_is_error: false,
+
             items: Vec::new(),
+            items_offset: 0,
+            item_count: 0,
+            total: 0,
+            unconverted: 0,
+            table_scroll: 0,
+            expanded: BTreeSet::new(),
+            job: None,
+            busy: None,
+            data_version: 0,
+            trash: Vec::new(),
             history: Vec::new(),
             distinct_categories: Vec::new(),
             distinct_descriptions: Vec::new(),
+            distinct_stores: Vec::new(),
+            distinct_tags: Vec::new(),
+            distinct_members: Vec::new(),
+            filter: ItemFilter::default(),
+            search_error: false,
+            saved_filters: BTreeMap::new(),
+            store_stats: Vec::new(),
+            cheapest: Vec::new(),
+            category_tree: Vec::new(),
+            marked_categories: BTreeSet::new(),
+            balances: Vec::new(),
+            settlement: Vec::new(),
+            transfers: Vec::new(),
+            payer: String::new(),
+            category_separator: String::from(DEFAULT_CATEGORY_SEPARATOR),
+            sort: SortOrder::default(),
+            home_currency: String::new(),
+            rates: Rates::default(),
+            locale: Locale::default(),
+            minor_units: MinorUnits::default(),
+            tax_rates: TaxRates::default(),
+            tax_report: Vec::new(),
+            tax_yearly: false,
+            date_format: String::new(),
         }
     }
 
@@ -60,10 +236,63 @@ impl App<'_> {
         self.textarea.lines()[0].trim()
     }
 
+    // Text that completions must start with.  Tags and filter terms are
+    // completed one word at a time.
+    pub fn get_completion_prefix(&self) -> &str {
+        match self.state {
+            AppState::EditTags | AppState::FilterTags => {
+                let line = &self.textarea.lines()[0];
+                let start = line
+                    .rfind(crate::util::is_tag_separator)
+                    .map_or(0, |i| i + 1);
+                &line[start..]
+            }
+            AppState::Filter => {
+                let line = &self.textarea.lines()[0];
+                let start = line.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+                &line[start..]
+            }
+            AppState::SplitItem | AppState::Shares => {
+                let line = &self.textarea.lines()[0];
+                let start = line.rfind(';').map_or(0, |i| i + 1);
+                line[start..].trim_start()
+            }
+            _ => self.get_text(),
+        }
+    }
+
     pub fn update_history(&mut self) {
+        let names: Vec<String>;
         let list = match self.state {
             AppState::InsertDescription => &self.distinct_descriptions,
-            AppState::InsertCategory => &self.distinct_categories,
+            AppState::InsertCategory | AppState::RenameCategory | AppState::MergeCategories => {
+                &self.distinct_categories
+            }
+            AppState::SplitItem if !self.get_completion_prefix().contains('=') => {
+                &self.distinct_categories
+            }
+            AppState::Payer => &self.distinct_members,
+            AppState::Shares if !self.get_completion_prefix().contains('=') => {
+                &self.distinct_members
+            }
+            AppState::InsertStore | AppState::ReceiptStore => &self.distinct_stores,
+            AppState::EditTags | AppState::FilterTags => &self.distinct_tags,
+            AppState::Filter if self.get_completion_prefix().starts_with('@') => {
+                names = self
+                    .saved_filters
+                    .keys()
+                    .map(|k| format!("@{}", k))
+                    .collect();
+                &names
+            }
+            AppState::SaveFilter => {
+                names = self.saved_filters.keys().cloned().collect();
+                &names
+            }
+            AppState::TaxRate | AppState::CategoryTax => {
+                names = self.tax_rates.rates.keys().cloned().collect();
+                &names
+            }
             _ => {
                 if !self.history.is_empty() {
                     self.history = Vec::new();
@@ -73,13 +302,36 @@ impl App<'_> {
             }
         };
 
-        let text = self.get_text();
+        let text = self.get_completion_prefix();
 
-        self.history = list
-            .iter()
-            .filter(|li| li.starts_with(text))
-            .cloned()
-            .collect();
+        let is_category = matches!(
+            self.state,
+            AppState::InsertCategory
+                | AppState::RenameCategory
+                | AppState::MergeCategories
+                | AppState::SplitItem
+        );
+
+        self.history = if is_category {
+            // Complete one level at a time, e.g. "Fo" offers "Food:" for
+            // "Food:Dairy" and "Food:Drinks"
+            let separator = &self.category_separator;
+            let mut levels: Vec<String> = list
+                .iter()
+                .filter(|li| li.starts_with(text))
+                .map(|li| match li[text.len()..].find(separator.as_str()) {
+                    Some(i) => String::from(&li[..text.len() + i + separator.len()]),
+                    None => li.clone(),
+                })
+                .collect();
+            levels.dedup();
+            levels
+        } else {
+            list.iter()
+                .filter(|li| li.starts_with(text))
+                .cloned()
+                .collect()
+        };
 
         if self.history.len() == 1 {
             self.list_state.select(Some(0));
@@ -88,6 +340,135 @@ impl App<'_> {
         }
     }
 
+    // Amount of the item in the home currency, if it can be converted.
+    pub fn home_price(&self, item: &DbItem) -> Option<i64> {
+        self.home_amount(item.price, &item.currency, &item.date)
+    }
+
+    // Amount in the given currency on the given date, converted to the home
+    // currency.
+    pub fn home_amount(&self, amount: i64, currency: &str, date: &str) -> Option<i64> {
+        if currency.is_empty() || currency == self.home_currency {
+            Some(amount)
+        } else if self.home_currency.is_empty() {
+            None
+        } else {
+            let shift = self.exponent("") as i32 - self.exponent(currency) as i32;
+            self.rates
+                .convert(amount, currency, &self.home_currency, date, shift)
+        }
+    }
+
+    // Digits after the decimal point of amounts in the given currency,
+    // where an empty currency is the home currency.
+    pub fn exponent(&self, currency: &str) -> u32 {
+        match currency {
+            "" if self.home_currency.is_empty() => 2,
+            "" => self.minor_units.exponent(&self.home_currency),
+            _ => self.minor_units.exponent(currency),
+        }
+    }
+
+    // Amount without a currency, e.g. a converted amount in the home
+    // currency.
+    pub fn format_price(&self, price: i64, currency: &str) -> String {
+        self.locale.format_price(price, self.exponent(currency))
+    }
+
+    // Amount followed by its currency code, or with the symbol of the home
+    // currency if one is configured.  An empty currency is the home
+    // currency.
+    pub fn format_money(&self, price: i64, currency: &str) -> String {
+        let is_home = currency.is_empty() || currency == self.home_currency;
+        if is_home && !self.locale.symbol.is_empty() {
+            self.locale.format_symbol(price, self.exponent(currency))
+        } else if currency.is_empty() {
+            self.format_price(price, currency)
+        } else {
+            format!("{} {}", self.format_price(price, currency), currency)
+        }
+    }
+
+    // Whether a currency code is in use: the home currency, one with
+    // exchange rates, or one whose minor unit is known.
+    pub fn is_known_currency(&self, code: &str) -> bool {
+        code == self.home_currency || self.rates.knows(code) || self.minor_units.knows(code)
+    }
+
+    // Amount with an optional currency code or symbol before or after it,
+    // e.g. "12,50 USD", "-$1,234.50" or "1 234,50 €".  The amount may be
+    // worked out from arithmetic, e.g. "3x1.29 USD", see expr.rs.  The
+    // currency is empty when none is given.  Words after the amount are a
+    // note and ignored, e.g. "4.99 - 0.50 coupon", and so are three-letter
+    // words that are not a known currency, e.g. "4.99 - 0.50 off".
+    pub fn parse_money(&self, line: &str) -> Option<(i64, String)> {
+        let mut line = line.trim();
+        loop {
+            if let Some(money) = self.parse_amount(line) {
+                return Some(money);
+            }
+            let (rest, word) = line.rsplit_once(char::is_whitespace)?;
+            if !word.chars().all(char::is_alphabetic) {
+                return None;
+            }
+            line = rest.trim_end();
+        }
+    }
+
+    fn parse_amount(&self, line: &str) -> Option<(i64, String)> {
+        // A minus sign may come before a currency symbol, e.g. "-$1.50"
+        let known = |code: &str| self.is_known_currency(code);
+        let (negative, currency, text) = if let Some((currency, text)) = self
+            .locale
+            .currency_suffix(line, known)
+            .or_else(|| self.locale.currency_prefix(line, known))
+        {
+            (false, currency, text)
+        } else if let Some((currency, text)) = line
+            .strip_prefix('-')
+            .and_then(|rest| self.locale.currency_prefix(rest, known))
+        {
+            if text.starts_with(['-', '+']) {
+                return None;
+            }
+            (true, currency, text)
+        } else {
+            (false, String::new(), line)
+        };
+
+        let price = expr::evaluate(&self.locale, text, self.exponent(&currency))?;
+        Some((if negative { -price } else { price }, currency))
+    }
+
+    // Compare the items entered against the printed total of the receipt.
+    // One left without items was never saved, see main_insert_price.
+    fn finish_receipt(&mut self) {
+        let receipt = match self.receipt.take() {
+            Some(receipt) if receipt.id != 0 => receipt,
+            _ => return,
+        };
+
+        let diff = self.receipt_sum - receipt.total;
+        let total = self.format_money(receipt.total, &receipt.currency);
+
+        self.message_is_error = diff != 0;
+        self.message = if diff == 0 {
+            format!(
+                "Receipt {}: items match printed total {}",
+                receipt.id, total
+            )
+        } else {
+            format!(
+                "Receipt {}: items sum to {}, {} {} printed total {}",
+                receipt.id,
+                self.format_money(self.receipt_sum, &receipt.currency),
+                self.format_money(diff.abs(), &receipt.currency),
+                if diff > 0 { "over" } else { "under" },
+                total
+            )
+        };
+    }
+
     pub fn transition(&mut self, state: AppState) {
         if self.state == state {
             return;
@@ -97,8 +478,32 @@ impl App<'_> {
         self.textarea = TextArea::default();
 
         match state {
-            AppState::Browse => self.item_template = None,
-            AppState::InsertDate => self.textarea.set_placeholder_text("yyyy-mm-dd"),
+            AppState::Browse => {
+                self.item_template = None;
+                self.finish_receipt();
+            }
+            AppState::InsertDate | AppState::ReceiptDate | AppState::SettleUp => self
+                .textarea
+                .set_placeholder_text("yyyy-mm-dd, today, -3, mon, 18"),
+            AppState::ReceiptTotal => self.textarea.set_placeholder_text("printed total"),
+            AppState::EmptyTrash => self.textarea.set_placeholder_text("0 for all"),
+            AppState::PurgeItem => self.textarea.set_placeholder_text("y to purge for good"),
+            AppState::SplitItem => self
+                .textarea
+                .set_placeholder_text("Food=50; Household=30.40; Pet"),
+            AppState::Payer => self
+                .textarea
+                .set_placeholder_text("member, or empty for none"),
+            AppState::Shares => self
+                .textarea
+                .set_placeholder_text("Ann; Bob, or Ann=60%; Bob=40%, or Ann=10; Bob"),
+            AppState::ImportRates => self
+                .textarea
+                .set_placeholder_text("CSV file of date,from,to,rate"),
+            AppState::InsertQuantity => self.textarea.set_placeholder_text("e.g. 2 kg, or empty"),
+            AppState::Filter => self
+                .textarea
+                .set_placeholder_text("e.g. category:Food price>20"),
             _ => (),
         };
 
@@ -110,10 +515,70 @@ impl App<'_> {
 pub enum AppState {
     Browse,
 
+    // F5: Per-store statistics
+    Stores,
+
+    // c: Category tree with subtotals
+    Categories,
+
+    // r: Rename category with subcategories, m: Merge marked categories
+    RenameCategory,
+    MergeCategories,
+
+    // x: Deleted items
+    Trash,
+
+    // e: Empty trash older than a number of days
+    EmptyTrash,
+
+    // F8: Purge the selected deleted item, once confirmed with y
+    PurgeItem,
+
+    // i: Import exchange rates from a file
+    ImportRates,
+
+    // F6: New receipt, then its items via InsertDescription
+    ReceiptDate,
+    ReceiptStore,
+    ReceiptTotal,
+
+    // F9: Edit tags of selected item
+    EditTags,
+
+    // t: Filter by tags
+    FilterTags,
+
+    // /: Full-text search
+    Search,
+
+    // f: Filter by field, s: Save the filter under a name
+    Filter,
+    SaveFilter,
+
+    // T: Tax rate of selected item, t in Categories: of a category
+    TaxRate,
+    CategoryTax,
+
+    // V: Tax paid per rate and period
+    TaxReport,
+
+    // S: Split selected item across categories
+    SplitItem,
+
+    // P: Member who paid for selected item, then how it is shared
+    Payer,
+    Shares,
+
+    // B: Who owes whom, s: Settle up on a date
+    Balances,
+    SettleUp,
+
     // F4: Edit, F7: Insert
     InsertDate,
     InsertDescription,
     InsertCategory,
+    InsertStore,
+    InsertQuantity,
     InsertPrice,
 }
 
@@ -124,4 +589,214 @@ pub struct DbItem {
     pub category: String,
     pub description: String,
     pub price: i64,
+
+    // Currency code, or empty for the home currency.
+    pub currency: String,
+
+    // Amount bought, in the given unit, or empty if not recorded.
+    pub quantity: f64,
+    pub unit: String,
+
+    // Where the item was bought, or empty if not recorded.
+    pub store: String,
+
+    // Receipt the item was entered from.
+    pub receipt_id: Option<i64>,
+
+    // Tags in alphabetical order.
+    pub tags: Vec<String>,
+
+    // Local time the item was moved to the trash.
+    pub deleted_at: Option<String>,
+
+    // Refunds and discounts have negative prices, and may refer to the
+    // purchase they apply to.
+    pub kind: ItemKind,
+    pub original_id: Option<i64>,
+
+    // Name of the tax rate if it differs from that of the category.
+    pub tax_rate: Option<String>,
+
+    // Allocations of the price to categories, if the item is split.
+    pub splits: Vec<Split>,
+
+    // Member of the household who paid, or empty if not recorded, and how
+    // the item is shared between members, if it is.
+    pub payer: String,
+    pub shares: Vec<Share>,
+}
+
+impl DbItem {
+    // Categories the price counts towards, with the amount of each: the
+    // allocations of a split item, or else its own category and price.
+    pub fn allocations(&self) -> Vec<(&str, i64)> {
+        if self.splits.is_empty() {
+            return vec![(self.category.as_str(), self.price)];
+        }
+        self.splits
+            .iter()
+            .map(|split| (split.category.as_str(), split.price))
+            .collect()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ItemKind {
+    #[default]
+    Purchase,
+    Refund,
+    Discount,
+}
+
+impl ItemKind {
+    // Value of the kind column in the items table.
+    pub fn sql(self) -> &'static str {
+        match self {
+            ItemKind::Purchase => "purchase",
+            ItemKind::Refund => "refund",
+            ItemKind::Discount => "discount",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<ItemKind> {
+        [ItemKind::Purchase, ItemKind::Refund, ItemKind::Discount]
+            .into_iter()
+            .find(|kind| kind.sql() == s)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DbReceipt {
+    pub id: i64,
+    pub date: String,
+    pub store: String,
+
+    // Total printed on the receipt.
+    pub total: i64,
+    pub currency: String,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SortColumn {
+    Date,
+    Category,
+    Description,
+    Price,
+    Id,
+}
+
+impl SortColumn {
+    const ALL: [SortColumn; 5] = [
+        SortColumn::Date,
+        SortColumn::Category,
+        SortColumn::Description,
+        SortColumn::Price,
+        SortColumn::Id,
+    ];
+
+    pub fn next(self) -> SortColumn {
+        let i = SortColumn::ALL.iter().position(|&c| c == self).unwrap();
+        SortColumn::ALL[(i + 1) % SortColumn::ALL.len()]
+    }
+
+    // Column name in the items table, also used as the saved setting.
+    pub fn sql(self) -> &'static str {
+        match self {
+            SortColumn::Date => "date",
+            SortColumn::Category => "category",
+            SortColumn::Description => "description",
+            SortColumn::Price => "price",
+            SortColumn::Id => "id",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SortOrder {
+    pub column: SortColumn,
+    pub descending: bool,
+}
+
+impl SortOrder {
+    pub fn parse(s: &str) -> Option<SortOrder> {
+        let (name, descending) = match s.strip_suffix(" desc") {
+            Some(name) => (name, true),
+            None => (s, false),
+        };
+        let column = *SortColumn::ALL.iter().find(|c| c.sql() == name)?;
+
+        Some(SortOrder { column, descending })
+    }
+
+    // Order of two items in the table, the same as the ORDER BY in db.rs.
+    pub fn compare(self, a: &DbItem, b: &DbItem) -> Ordering {
+        let ordering = match self.column {
+            SortColumn::Date => a.date.cmp(&b.date),
+            SortColumn::Category => a.category.cmp(&b.category),
+            SortColumn::Description => a.description.cmp(&b.description),
+            SortColumn::Price => a.price.cmp(&b.price),
+            SortColumn::Id => Ordering::Equal,
+        }
+        .then(a.id.cmp(&b.id));
+
+        if self.descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+
+    pub fn to_setting(self) -> String {
+        if self.descending {
+            format!("{} desc", self.column.sql())
+        } else {
+            String::from(self.column.sql())
+        }
+    }
+}
+
+impl Default for SortOrder {
+    fn default() -> SortOrder {
+        SortOrder {
+            column: SortColumn::Date,
+            descending: false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TagFilter {
+    pub tags: Vec<String>,
+
+    // Whether items need all of the tags, rather than any of them.
+    pub all: bool,
+}
+
+impl TagFilter {
+    // "work gift" matches items with either tag, "work+gift" needs both.
+    pub fn parse(line: &str) -> TagFilter {
+        TagFilter {
+            tags: crate::util::split_tags(line),
+            all: line.contains('+'),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty()
+    }
+
+    pub fn to_line(&self) -> String {
+        self.tags.join(if self.all { "+" } else { " " })
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ItemFilter {
+    pub tags: TagFilter,
+
+    // Full-text query on description and category, in FTS5 syntax.
+    pub search: String,
+
+    // Conditions on fields, see filter.rs.
+    pub query: Option<Query>,
 }
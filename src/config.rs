@@ -0,0 +1,215 @@
+use std::path::PathBuf;
+
+const DATABASE_FILE: &str = "shopping.db";
+const DATABASE_ENV: &str = "SHOPPINGDB_FILE";
+
+const USAGE: &str =
+    "Usage: shoppingdb [--db <path>] [--set <key>=<value>]... [--import-rates <csv>]
+                  [--tax-rate <name>=<percent>]...
+
+Options:
+  --db <path>            Database file to open (default: $SHOPPINGDB_FILE, or
+                         $XDG_DATA_HOME/shoppingdb/shopping.db)
+  --set <key>=<value>    Store a setting in the database and exit
+  --import-rates <csv>   Import exchange rates (date,from,to,rate) and exit
+  --tax-rate <name>=<percent>
+                         Add or change a tax rate, e.g. standard=19, and exit;
+                         an empty percent deletes it
+  --help                 Show this message
+
+Settings:";
+
+// Settings that may be changed with --set, and what they mean.
+pub const SETTINGS: &[(&str, &str)] = &[
+    (
+        "category_separator",
+        "separator between category levels, e.g. \":\"",
+    ),
+    (
+        "currency_symbol",
+        "symbol shown for the home currency, e.g. \"€\", or empty",
+    ),
+    (
+        "date_format",
+        "local format dates may be typed in, e.g. \"%d.%m.%Y\"",
+    ),
+    (
+        "decimal_separator",
+        "\".\" or \",\" between whole units and fractions",
+    ),
+    (
+        "grouping_separator",
+        "separator of thousands, e.g. \",\" or \" \", or empty",
+    ),
+    (
+        "home_currency",
+        "currency code that totals are converted to",
+    ),
+    (
+        "minor_units",
+        "digits after the decimal point, e.g. \"JPY=0,KWD=3\"",
+    ),
+    ("symbol_position", "\"before\" or \"after\" the amount"),
+    ("sort", "table order, e.g. \"date\" or \"price desc\""),
+];
+
+pub struct Config {
+    // Path to the database file.
+    pub database: PathBuf,
+
+    // Settings to store before exiting.
+    pub settings: Vec<(String, String)>,
+
+    // Exchange rate file to import before exiting.
+    pub import_rates: Option<PathBuf>,
+
+    // Tax rates to add, change or, without a rate, delete before exiting.
+    pub tax_rates: Vec<(String, Option<i64>)>,
+}
+
+impl Config {
+    pub fn from_args() -> Result<Config, Box<dyn std::error::Error>> {
+        let mut database = None;
+        let mut settings = Vec::new();
+        let mut import_rates = None;
+        let mut tax_rates = Vec::new();
+        let mut args = std::env::args().skip(1);
+
+        while let Some(arg) = args.next() {
+            if arg == "--help" || arg == "-h" {
+                println!("{}", USAGE);
+                for (key, help) in SETTINGS {
+                    println!("  {:<21}  {}", key, help);
+                }
+                std::process::exit(0);
+            } else if arg == "--db" {
+                match args.next() {
+                    Some(path) => database = Some(PathBuf::from(path)),
+                    None => return Err("--db requires a path".into()),
+                }
+            } else if let Some(path) = arg.strip_prefix("--db=") {
+                database = Some(PathBuf::from(path));
+            } else if arg == "--set" {
+                let setting = args.next().ok_or("--set requires <key>=<value>")?;
+                settings.push(parse_setting(&setting)?);
+            } else if arg == "--import-rates" {
+                let path = args.next().ok_or("--import-rates requires a path")?;
+                import_rates = Some(PathBuf::from(path));
+            } else if arg == "--tax-rate" {
+                let rate = args.next().ok_or("--tax-rate requires <name>=<percent>")?;
+                tax_rates.push(parse_tax_rate(&rate)?);
+            } else {
+                return Err(format!("unknown argument '{}'\n\n{}", arg, USAGE).into());
+            }
+        }
+
+        let database = database
+            .or_else(|| std::env::var_os(DATABASE_ENV).map(PathBuf::from))
+            .or_else(|| data_dir().map(|dir| dir.join("shoppingdb").join(DATABASE_FILE)))
+            .unwrap_or_else(|| PathBuf::from(DATABASE_FILE));
+
+        Ok(Config {
+            database,
+            settings,
+            import_rates,
+            tax_rates,
+        })
+    }
+
+    // Whether to run a one-off command instead of the user interface.
+    pub fn is_batch(&self) -> bool {
+        !self.settings.is_empty() || self.import_rates.is_some() || !self.tax_rates.is_empty()
+    }
+}
+
+fn parse_setting(arg: &str) -> Result<(String, String), Box<dyn std::error::Error>> {
+    let (key, value) = arg
+        .split_once('=')
+        .ok_or_else(|| format!("expected <key>=<value>, got '{}'", arg))?;
+
+    let value = match key {
+        "category_separator" if !value.is_empty() => String::from(value),
+        "currency_symbol" if !value.contains(|c: char| c.is_ascii_digit()) => {
+            String::from(value.trim())
+        }
+        "date_format" if value.is_empty() || crate::util::is_date_format(value) => {
+            String::from(value)
+        }
+        "decimal_separator" if value == "." || value == "," => String::from(value),
+        "grouping_separator"
+            if value.chars().count() <= 1
+                && !value.contains(|c: char| c.is_ascii_digit() || c == '-') =>
+        {
+            String::from(value)
+        }
+        "symbol_position" if value == "before" || value == "after" => String::from(value),
+        "minor_units" => crate::currency::MinorUnits::parse(value)
+            .ok_or_else(|| format!("'{}' is not a list of <code>=<digits>", value))?
+            .to_setting(),
+        "home_currency" => crate::currency::parse_code(value)
+            .ok_or_else(|| format!("'{}' is not a currency code", value))?,
+        "sort" => crate::app::SortOrder::parse(value)
+            .ok_or_else(|| format!("'{}' is not a sort order", value))?
+            .to_setting(),
+        "category_separator" | "currency_symbol" | "date_format" | "decimal_separator"
+        | "grouping_separator" | "symbol_position" => {
+            return Err(format!("'{}' is not a valid {}", value, key).into())
+        }
+        _ => return Err(format!("unknown setting '{}'", key).into()),
+    };
+
+    Ok((String::from(key), value))
+}
+
+fn parse_tax_rate(arg: &str) -> Result<(String, Option<i64>), Box<dyn std::error::Error>> {
+    let (name, percent) = arg
+        .split_once('=')
+        .ok_or_else(|| format!("expected <name>=<percent>, got '{}'", arg))?;
+
+    let name = name.trim();
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return Err(format!("'{}' is not a valid tax rate name", name).into());
+    }
+    if percent.is_empty() {
+        return Ok((String::from(name), None));
+    }
+
+    let rate = crate::tax::parse_rate(percent)
+        .ok_or_else(|| format!("'{}' is not a percentage", percent))?;
+    Ok((String::from(name), Some(rate)))
+}
+
+// $XDG_DATA_HOME, falling back to ~/.local/share as the spec requires.
+fn data_dir() -> Option<PathBuf> {
+    match std::env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir)),
+        _ => std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")),
+    }
+}
+
+// Ask before creating a new database, so that a mistyped path does not
+// silently start an empty one.
+pub fn confirm_create(path: &std::path::Path) -> Result<bool, Box<dyn std::error::Error>> {
+    use std::io::Write;
+
+    eprint!(
+        "Database {} does not exist. Create it? [y/N] ",
+        path.display()
+    );
+    std::io::stderr().flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+
+    if !answer.trim().eq_ignore_ascii_case("y") {
+        return Ok(false);
+    }
+
+    if let Some(dir) = path.parent() {
+        if !dir.as_os_str().is_empty() {
+            std::fs::create_dir_all(dir)?;
+        }
+    }
+
+    Ok(true)
+}
@@ -0,0 +1,224 @@
+use std::collections::{BTreeMap, HashMap};
+
+// Exchange rates queried from database, keyed by (from, to) currency.
+// Each list holds (date, rate) pairs sorted by date, where one unit of
+// `from` is worth `rate` units of `to`.
+#[derive(Default)]
+pub struct Rates {
+    map: HashMap<(String, String), Vec<(String, f64)>>,
+}
+
+impl Rates {
+    pub fn new(rows: Vec<Rate>) -> Rates {
+        let mut map: HashMap<(String, String), Vec<(String, f64)>> = HashMap::new();
+
+        for row in rows {
+            map.entry((row.from, row.to))
+                .or_default()
+                .push((row.date, row.rate));
+        }
+
+        for list in map.values_mut() {
+            list.sort_by(|a, b| a.0.cmp(&b.0));
+        }
+
+        Rates { map }
+    }
+
+    // Convert an amount in minor units, using the most recent rate on or
+    // before `date`, or the earliest known rate if there is none.  Falls
+    // back to the inverse of the (to, from) rate.  `shift` is the number of
+    // minor-unit digits `to` has more than `from`.
+    pub fn convert(
+        &self,
+        amount: i64,
+        from: &str,
+        to: &str,
+        date: &str,
+        shift: i32,
+    ) -> Option<i64> {
+        if from == to {
+            return Some(amount);
+        }
+
+        let rate = if let Some(rate) = self.lookup(from, to, date) {
+            rate
+        } else {
+            1.0 / self.lookup(to, from, date)?
+        };
+
+        Some((amount as f64 * rate * 10f64.powi(shift)).round() as i64)
+    }
+
+    // Whether there is any rate from or to the currency.
+    pub fn knows(&self, code: &str) -> bool {
+        self.map.keys().any(|(from, to)| from == code || to == code)
+    }
+
+    fn lookup(&self, from: &str, to: &str, date: &str) -> Option<f64> {
+        let list = self.map.get(&(String::from(from), String::from(to)))?;
+        let i = list.partition_point(|(d, _)| d.as_str() <= date);
+
+        list.get(i.saturating_sub(1)).map(|&(_, rate)| rate)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Rate {
+    pub date: String,
+    pub from: String,
+    pub to: String,
+    pub rate: f64,
+}
+
+// Digits after the decimal point of currencies where there are not two,
+// as in ISO 4217.
+const EXPONENTS: &[(&str, u32)] = &[
+    ("BHD", 3),
+    ("BIF", 0),
+    ("CLP", 0),
+    ("DJF", 0),
+    ("GNF", 0),
+    ("IQD", 3),
+    ("ISK", 0),
+    ("JOD", 3),
+    ("JPY", 0),
+    ("KMF", 0),
+    ("KRW", 0),
+    ("KWD", 3),
+    ("LYD", 3),
+    ("OMR", 3),
+    ("PYG", 0),
+    ("RWF", 0),
+    ("TND", 3),
+    ("UGX", 0),
+    ("VND", 0),
+    ("VUV", 0),
+    ("XAF", 0),
+    ("XOF", 0),
+    ("XPF", 0),
+];
+
+// Most digits after the decimal point any currency may have, which keeps
+// amounts in minor units well within i64.
+pub const MAX_EXPONENT: u32 = 4;
+
+// Digits after the decimal point per currency, i.e. the exponent of its
+// minor unit: 2 for cents, 0 for yen, 3 for fils.  Prices are stored as
+// whole numbers of minor units.  The minor_units setting, e.g.
+// "JPY=0,KWD=3", adds to or overrides the ISO 4217 values.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MinorUnits {
+    overrides: BTreeMap<String, u32>,
+}
+
+impl MinorUnits {
+    pub fn parse(setting: &str) -> Option<MinorUnits> {
+        let mut overrides = BTreeMap::new();
+
+        for entry in setting.split([',', ' ']).filter(|entry| !entry.is_empty()) {
+            let (code, exponent) = entry.split_once('=')?;
+            let exponent = exponent.parse().ok().filter(|&e| e <= MAX_EXPONENT)?;
+            overrides.insert(parse_code(code)?, exponent);
+        }
+
+        Some(MinorUnits { overrides })
+    }
+
+    pub fn to_setting(&self) -> String {
+        let entries: Vec<String> = self
+            .overrides
+            .iter()
+            .map(|(code, exponent)| format!("{}={}", code, exponent))
+            .collect();
+        entries.join(",")
+    }
+
+    pub fn exponent(&self, code: &str) -> u32 {
+        if let Some(&exponent) = self.overrides.get(code) {
+            return exponent;
+        }
+        match EXPONENTS.binary_search_by(|&(c, _)| c.cmp(code)) {
+            Ok(i) => EXPONENTS[i].1,
+            Err(_) => 2,
+        }
+    }
+
+    // Whether the exponent of the currency is set or listed in ISO 4217,
+    // rather than assumed to be 2.
+    pub fn knows(&self, code: &str) -> bool {
+        self.overrides.contains_key(code)
+            || EXPONENTS.binary_search_by(|&(c, _)| c.cmp(code)).is_ok()
+    }
+
+    // Add the overrides of another setting, replacing any for the same
+    // currencies.
+    pub fn merge(&mut self, other: &MinorUnits) {
+        self.overrides.extend(other.overrides.clone());
+    }
+
+    pub fn codes(&self) -> impl Iterator<Item = &String> {
+        self.overrides.keys()
+    }
+
+    // Every currency whose exponent is known, which is 2 for all others.
+    pub fn list(&self) -> BTreeMap<String, u32> {
+        let mut list: BTreeMap<String, u32> = EXPONENTS
+            .iter()
+            .map(|&(code, exponent)| (String::from(code), exponent))
+            .collect();
+        list.extend(self.overrides.clone());
+        list
+    }
+}
+
+// Currency codes are three ASCII letters, as in ISO 4217.
+pub fn parse_code(s: &str) -> Option<String> {
+    if s.len() == 3 && s.chars().all(|c| c.is_ascii_alphabetic()) {
+        Some(s.to_ascii_uppercase())
+    } else {
+        None
+    }
+}
+
+// Read exchange rates from CSV lines of the form
+//
+//     date,from,to,rate
+//     2026-10-01,USD,EUR,0.92
+//
+// A header line and blank lines are skipped.
+pub fn read_csv(path: &std::path::Path) -> Result<Vec<Rate>, Box<dyn std::error::Error>> {
+    let text = std::fs::read_to_string(path)?;
+    let mut rates = Vec::new();
+
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || (n == 0 && line.starts_with("date")) {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+        let rate = match fields[..] {
+            [date, from, to, rate] => crate::util::parse_date(date).and_then(|date| {
+                Some(Rate {
+                    date: date.format("%F").to_string(),
+                    from: parse_code(from)?,
+                    to: parse_code(to)?,
+                    rate: rate.parse::<f64>().ok().filter(|r| *r > 0.0)?,
+                })
+            }),
+            _ => None,
+        };
+
+        match rate {
+            Some(rate) => rates.push(rate),
+            None => {
+                return Err(
+                    format!("{}:{}: malformed rate '{}'", path.display(), n + 1, line).into(),
+                )
+            }
+        }
+    }
+
+    Ok(rates)
+}
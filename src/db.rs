@@ -0,0 +1,1482 @@
+use crate::app::{DbItem, DbReceipt, ItemFilter, ItemKind, SortColumn, SortOrder, TagFilter};
+use crate::currency::Rate;
+use crate::household::{Share, Transfer};
+use crate::journal::Step;
+use crate::split::Split;
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Result, Transaction};
+use std::collections::BTreeMap;
+
+// Schema upgrade steps, applied in order.  The database records how many
+// of these have been applied in `PRAGMA user_version`.  Never edit or
+// reorder an existing entry; append a new one instead.
+const MIGRATIONS: &[&str] = &[
+    // 1: initial schema.  Databases created before versioning already have
+    // this table, hence IF NOT EXISTS.
+    "CREATE TABLE IF NOT EXISTS items (
+         id INTEGER PRIMARY KEY,
+         date TEXT NOT NULL,
+         category TEXT NOT NULL,
+         description TEXT NOT NULL,
+         price INTEGER NOT NULL
+     );",
+    // 2: persistent user preferences.
+    "CREATE TABLE settings (
+         key TEXT PRIMARY KEY,
+         value TEXT NOT NULL
+     );",
+    // 3: currency per item, where '' is the home currency, and exchange
+    // rates between pairs of currencies.
+    "ALTER TABLE items ADD COLUMN currency TEXT NOT NULL DEFAULT '';
+     CREATE TABLE exchange_rates (
+         date TEXT NOT NULL,
+         from_currency TEXT NOT NULL,
+         to_currency TEXT NOT NULL,
+         rate REAL NOT NULL,
+         PRIMARY KEY (date, from_currency, to_currency)
+     );",
+    // 4: quantity and unit per item, where '' means not recorded.
+    "ALTER TABLE items ADD COLUMN quantity REAL NOT NULL DEFAULT 1;
+     ALTER TABLE items ADD COLUMN unit TEXT NOT NULL DEFAULT '';",
+    // 5: where items were bought.
+    "CREATE TABLE stores (
+         id INTEGER PRIMARY KEY,
+         name TEXT NOT NULL UNIQUE
+     );
+     ALTER TABLE items ADD COLUMN store_id INTEGER REFERENCES stores(id);",
+    // 6: receipts grouping items bought together, with the printed total.
+    "CREATE TABLE receipts (
+         id INTEGER PRIMARY KEY,
+         date TEXT NOT NULL,
+         store_id INTEGER REFERENCES stores(id),
+         total INTEGER NOT NULL,
+         currency TEXT NOT NULL DEFAULT ''
+     );
+     ALTER TABLE items ADD COLUMN receipt_id INTEGER REFERENCES receipts(id);",
+    // 7: free-form tags, many per item.
+    "CREATE TABLE tags (
+         id INTEGER PRIMARY KEY,
+         name TEXT NOT NULL UNIQUE
+     );
+     CREATE TABLE item_tags (
+         item_id INTEGER NOT NULL REFERENCES items(id),
+         tag_id INTEGER NOT NULL REFERENCES tags(id),
+         PRIMARY KEY (item_id, tag_id)
+     );",
+    // 8: undo/redo journal of changes to items, see journal.rs.
+    "CREATE TABLE journal (
+         id INTEGER PRIMARY KEY,
+         step INTEGER NOT NULL,
+         time TEXT NOT NULL DEFAULT (datetime('now')),
+         item_id INTEGER NOT NULL,
+         before TEXT,
+         after TEXT,
+         undone INTEGER NOT NULL DEFAULT 0
+     );
+     CREATE INDEX journal_step ON journal(step);",
+    // 9: soft delete, where NULL means the item is not in the trash.
+    "ALTER TABLE items ADD COLUMN deleted_at TEXT;",
+    // 10: full-text index on description and category, kept in sync with
+    // the items table by triggers.
+    "CREATE VIRTUAL TABLE items_fts USING fts5(
+         description, category, content='items', content_rowid='id'
+     );
+     CREATE TRIGGER items_fts_insert AFTER INSERT ON items BEGIN
+         INSERT INTO items_fts(rowid, description, category)
+             VALUES (new.id, new.description, new.category);
+     END;
+     CREATE TRIGGER items_fts_delete AFTER DELETE ON items BEGIN
+         INSERT INTO items_fts(items_fts, rowid, description, category)
+             VALUES ('delete', old.id, old.description, old.category);
+     END;
+     CREATE TRIGGER items_fts_update AFTER UPDATE OF description, category ON items BEGIN
+         INSERT INTO items_fts(items_fts, rowid, description, category)
+             VALUES ('delete', old.id, old.description, old.category);
+         INSERT INTO items_fts(rowid, description, category)
+             VALUES (new.id, new.description, new.category);
+     END;
+     INSERT INTO items_fts(items_fts) VALUES ('rebuild');",
+    // 11: named filters for the browse table.
+    "CREATE TABLE filters (
+         name TEXT PRIMARY KEY,
+         query TEXT NOT NULL
+     );",
+    // 12: refunds and discounts, with negative prices, optionally linked to
+    // the purchase they apply to.
+    "ALTER TABLE items ADD COLUMN kind TEXT NOT NULL DEFAULT 'purchase';
+     ALTER TABLE items ADD COLUMN original_id INTEGER REFERENCES items(id);",
+    // 13: prices in minor units of their own currency, rather than always
+    // in hundredths, for currencies that ISO 4217 gives other than two
+    // digits after the decimal point.  See currency::MinorUnits.
+    //
+    // Amounts are rescaled in integers: three-digit currencies are
+    // multiplied by 10, and those without minor units are divided by 100,
+    // rounding half away from zero.
+    "CREATE TEMP TABLE widen (currency TEXT PRIMARY KEY);
+     CREATE TEMP TABLE narrow (currency TEXT PRIMARY KEY);
+     INSERT INTO widen VALUES
+         ('BHD'), ('IQD'), ('JOD'), ('KWD'), ('LYD'), ('OMR'), ('TND');
+     INSERT INTO narrow VALUES
+         ('BIF'), ('CLP'), ('DJF'), ('GNF'), ('ISK'), ('JPY'), ('KMF'), ('KRW'),
+         ('PYG'), ('RWF'), ('UGX'), ('VND'), ('VUV'), ('XAF'), ('XOF'), ('XPF');
+     INSERT INTO widen SELECT '' FROM widen
+         WHERE currency = (SELECT value FROM settings WHERE key = 'home_currency');
+     INSERT INTO narrow SELECT '' FROM narrow
+         WHERE currency = (SELECT value FROM settings WHERE key = 'home_currency');
+     UPDATE items SET price = price * 10
+         WHERE currency IN (SELECT currency FROM widen);
+     UPDATE items SET price = (price + CASE WHEN price < 0 THEN -50 ELSE 50 END) / 100
+         WHERE currency IN (SELECT currency FROM narrow);
+     UPDATE receipts SET total = total * 10
+         WHERE currency IN (SELECT currency FROM widen);
+     UPDATE receipts SET total = (total + CASE WHEN total < 0 THEN -50 ELSE 50 END) / 100
+         WHERE currency IN (SELECT currency FROM narrow);
+     UPDATE journal SET before = json_set(before, '$.price',
+             json_extract(before, '$.price') * 10)
+         WHERE json_extract(before, '$.currency') IN (SELECT currency FROM widen);
+     UPDATE journal SET before = json_set(before, '$.price',
+             (json_extract(before, '$.price')
+                 + CASE WHEN json_extract(before, '$.price') < 0 THEN -50 ELSE 50 END) / 100)
+         WHERE json_extract(before, '$.currency') IN (SELECT currency FROM narrow);
+     UPDATE journal SET after = json_set(after, '$.price',
+             json_extract(after, '$.price') * 10)
+         WHERE json_extract(after, '$.currency') IN (SELECT currency FROM widen);
+     UPDATE journal SET after = json_set(after, '$.price',
+             (json_extract(after, '$.price')
+                 + CASE WHEN json_extract(after, '$.price') < 0 THEN -50 ELSE 50 END) / 100)
+         WHERE json_extract(after, '$.currency') IN (SELECT currency FROM narrow);
+     DROP TABLE widen;
+     DROP TABLE narrow;",
+    // 14: tax rates in hundredths of a percent, the rate that applies to
+    // items of a category by default, and the rate of an item where it
+    // differs.  Prices include tax.
+    "CREATE TABLE tax_rates (
+         name TEXT PRIMARY KEY,
+         rate INTEGER NOT NULL
+     );
+     CREATE TABLE category_tax (
+         category TEXT PRIMARY KEY,
+         tax_rate TEXT NOT NULL REFERENCES tax_rates(name)
+     );
+     ALTER TABLE items ADD COLUMN tax_rate TEXT REFERENCES tax_rates(name);",
+    // 15: allocations of split items to categories, in order, in the
+    // item's currency.  They add up to the item's price.
+    "CREATE TABLE item_splits (
+         item_id INTEGER NOT NULL REFERENCES items(id),
+         position INTEGER NOT NULL,
+         category TEXT NOT NULL,
+         price INTEGER NOT NULL,
+         PRIMARY KEY (item_id, position)
+     );",
+    // 16: members of a household sharing the database, who paid for an
+    // item, and how it is shared: equally where percent and amount are
+    // NULL, else by percent in hundredths or by amount in the item's
+    // currency.  Transfers settle up between members in the home currency.
+    "CREATE TABLE members (
+         id INTEGER PRIMARY KEY,
+         name TEXT NOT NULL UNIQUE
+     );
+     ALTER TABLE items ADD COLUMN payer_id INTEGER REFERENCES members(id);
+     CREATE TABLE item_shares (
+         item_id INTEGER NOT NULL REFERENCES items(id),
+         position INTEGER NOT NULL,
+         member_id INTEGER NOT NULL REFERENCES members(id),
+         percent INTEGER,
+         amount INTEGER,
+         PRIMARY KEY (item_id, position)
+     );
+     CREATE TABLE transfers (
+         id INTEGER PRIMARY KEY,
+         date TEXT NOT NULL,
+         from_id INTEGER NOT NULL REFERENCES members(id),
+         to_id INTEGER NOT NULL REFERENCES members(id),
+         amount INTEGER NOT NULL
+     );",
+    // 17: tax rates of categories in the undo/redo journal, before and
+    // after a step, where NULL means the category had no rate of its own.
+    "CREATE TABLE journal_category_tax (
+         id INTEGER PRIMARY KEY,
+         step INTEGER NOT NULL,
+         category TEXT NOT NULL,
+         before TEXT,
+         after TEXT
+     );
+     CREATE INDEX journal_category_tax_step ON journal_category_tax(step);",
+];
+
+pub fn open(path: &std::path::Path) -> Result<Connection, Box<dyn std::error::Error>> {
+    let mut conn = Connection::open(path)?;
+    migrate(&mut conn)?;
+    Ok(conn)
+}
+
+fn migrate(conn: &mut Connection) -> Result<(), Box<dyn std::error::Error>> {
+    let latest = MIGRATIONS.len() as i64;
+    let tx = conn.transaction()?;
+    let version: i64 = tx.query_row("PRAGMA user_version", (), |row| row.get(0))?;
+
+    if version > latest {
+        return Err(format!(
+            "database schema version {} is newer than this program supports ({})",
+            version, latest
+        )
+        .into());
+    }
+
+    for (i, sql) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        tx.execute_batch(sql)?;
+        tx.pragma_update(None, "user_version", i as i64 + 1)?;
+    }
+
+    tx.commit()?;
+
+    Ok(())
+}
+
+pub fn get_setting(conn: &Connection, key: &str) -> Result<Option<String>> {
+    conn.query_row(
+        "SELECT value FROM settings WHERE key=?1",
+        params![key],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+pub fn set_setting(conn: &Connection, key: &str, value: &str) -> Result<()> {
+    conn.execute(
+        "INSERT INTO settings(key, value) VALUES (?1, ?2)
+            ON CONFLICT(key) DO UPDATE SET value=excluded.value",
+        params![key, value],
+    )?;
+
+    Ok(())
+}
+
+// Id of the named store, creating it if necessary.  An empty name is no store.
+fn store_id(conn: &Connection, name: &str) -> Result<Option<i64>> {
+    if name.is_empty() {
+        return Ok(None);
+    }
+
+    conn.execute(
+        "INSERT OR IGNORE INTO stores(name) VALUES (?1)",
+        params![name],
+    )?;
+
+    conn.query_row(
+        "SELECT id FROM stores WHERE name=?1",
+        params![name],
+        |row| row.get(0),
+    )
+    .map(Some)
+}
+
+// Id of the named member, adding them if necessary.  An empty name is no
+// member.
+fn member_id(conn: &Connection, name: &str) -> Result<Option<i64>> {
+    if name.is_empty() {
+        return Ok(None);
+    }
+
+    conn.execute(
+        "INSERT OR IGNORE INTO members(name) VALUES (?1)",
+        params![name],
+    )?;
+
+    conn.query_row(
+        "SELECT id FROM members WHERE name=?1",
+        params![name],
+        |row| row.get(0),
+    )
+    .map(Some)
+}
+
+pub fn insert_item(conn: &Connection, item: &DbItem) -> Result<i64> {
+    let tx = conn.unchecked_transaction()?;
+    let mut step = Step::begin(&tx)?;
+
+    let store_id = store_id(&tx, &item.store)?;
+    let payer_id = member_id(&tx, &item.payer)?;
+    let mut stmt = tx.prepare(
+        "INSERT INTO items(date, category, description, price, currency, quantity, unit,
+                store_id, receipt_id, kind, original_id, tax_rate, payer_id)
+            values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+    )?;
+
+    stmt.execute(params![
+        item.date,
+        item.category,
+        item.description,
+        item.price,
+        item.currency,
+        item.quantity,
+        item.unit,
+        store_id,
+        item.receipt_id,
+        item.kind.sql(),
+        item.original_id,
+        item.tax_rate,
+        payer_id
+    ])?;
+    drop(stmt);
+
+    let id = tx.last_insert_rowid();
+    set_splits(&tx, id, &item.splits)?;
+    set_shares(&tx, id, &item.shares)?;
+    step.track_new(id);
+    step.finish(&tx)?;
+    tx.commit()?;
+
+    Ok(id)
+}
+
+pub fn insert_receipt(conn: &Connection, receipt: &DbReceipt) -> Result<i64> {
+    let store_id = store_id(conn, &receipt.store)?;
+    conn.execute(
+        "INSERT INTO receipts(date, store_id, total, currency) VALUES (?1, ?2, ?3, ?4)",
+        params![receipt.date, store_id, receipt.total, receipt.currency],
+    )?;
+
+    Ok(conn.last_insert_rowid())
+}
+
+pub fn update_item(conn: &Connection, id: i64, item: &DbItem) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    let mut step = Step::begin(&tx)?;
+    step.track(&tx, id)?;
+
+    let store_id = store_id(&tx, &item.store)?;
+    let payer_id = member_id(&tx, &item.payer)?;
+    tx.execute(
+        "UPDATE items SET date=?1, category=?2, description=?3, price=?4, currency=?5,
+                quantity=?6, unit=?7, store_id=?8, receipt_id=?9, kind=?10, original_id=?11,
+                tax_rate=?12, payer_id=?13
+            WHERE id=?14",
+        params![
+            item.date,
+            item.category,
+            item.description,
+            item.price,
+            item.currency,
+            item.quantity,
+            item.unit,
+            store_id,
+            item.receipt_id,
+            item.kind.sql(),
+            item.original_id,
+            item.tax_rate,
+            payer_id,
+            id
+        ],
+    )?;
+    set_splits(&tx, id, &item.splits)?;
+    set_shares(&tx, id, &item.shares)?;
+
+    step.finish(&tx)?;
+    tx.commit()
+}
+
+fn set_shares(conn: &Connection, id: i64, shares: &[Share]) -> Result<()> {
+    conn.execute("DELETE FROM item_shares WHERE item_id=?1", params![id])?;
+
+    for (position, share) in shares.iter().enumerate() {
+        let member_id = member_id(conn, &share.member)?;
+        conn.execute(
+            "INSERT INTO item_shares(item_id, position, member_id, percent, amount)
+                VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![id, position, member_id, share.percent, share.amount],
+        )?;
+    }
+
+    Ok(())
+}
+
+fn set_splits(conn: &Connection, id: i64, splits: &[Split]) -> Result<()> {
+    conn.execute("DELETE FROM item_splits WHERE item_id=?1", params![id])?;
+
+    for (position, split) in splits.iter().enumerate() {
+        conn.execute(
+            "INSERT INTO item_splits(item_id, position, category, price) VALUES (?1, ?2, ?3, ?4)",
+            params![id, position, split.category, split.price],
+        )?;
+    }
+
+    Ok(())
+}
+
+pub fn delete_item(conn: &Connection, id: i64) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    let mut step = Step::begin(&tx)?;
+    step.track(&tx, id)?;
+
+    tx.execute(
+        "UPDATE items SET deleted_at=datetime('now', 'localtime') WHERE id=?1",
+        params![id],
+    )?;
+
+    step.finish(&tx)?;
+    tx.commit()
+}
+
+pub fn restore_item(conn: &Connection, id: i64) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    let mut step = Step::begin(&tx)?;
+    step.track(&tx, id)?;
+
+    tx.execute("UPDATE items SET deleted_at=NULL WHERE id=?1", params![id])?;
+
+    step.finish(&tx)?;
+    tx.commit()
+}
+
+// Called during long operations with the number of rows done so far and
+// the total.  Returning false cancels the operation, which then fails as
+// if interrupted and changes nothing.
+pub type Progress<'a> = &'a mut dyn FnMut(usize, usize) -> bool;
+
+fn cancelled() -> rusqlite::Error {
+    rusqlite::Error::SqliteFailure(
+        rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_INTERRUPT),
+        None,
+    )
+}
+
+// Whether the error comes from a cancelled or interrupted operation.
+pub fn is_cancelled(err: &rusqlite::Error) -> bool {
+    err.sqlite_error_code() == Some(rusqlite::ErrorCode::OperationInterrupted)
+}
+
+// Remove items from the trash for good, including the journal steps that
+// touched them.  With `days`, only items deleted at least that many days
+// ago.
+pub fn purge_items(
+    conn: &mut Connection,
+    id: Option<i64>,
+    days: Option<u32>,
+    progress: Progress,
+) -> Result<usize> {
+    let tx = conn.transaction()?;
+    let ids = select_ids(
+        &tx,
+        "SELECT id FROM items
+            WHERE deleted_at IS NOT NULL
+              AND (?1 IS NULL OR id = ?1)
+              AND (?2 IS NULL OR deleted_at <= datetime('now', 'localtime', '-' || ?2 || ' days'))",
+        params![id, days],
+    )?;
+
+    for (n, id) in ids.iter().enumerate() {
+        if !progress(n, ids.len()) {
+            return Err(cancelled());
+        }
+        crate::journal::forget(&tx, *id)?;
+        tx.execute("DELETE FROM item_tags WHERE item_id=?1", params![id])?;
+        tx.execute("DELETE FROM item_splits WHERE item_id=?1", params![id])?;
+        tx.execute("DELETE FROM item_shares WHERE item_id=?1", params![id])?;
+        tx.execute("DELETE FROM items WHERE id=?1", params![id])?;
+        tx.execute(
+            "UPDATE items SET original_id=NULL WHERE original_id=?1",
+            params![id],
+        )?;
+    }
+
+    tx.commit()?;
+
+    Ok(ids.len())
+}
+
+// Multiply prices in the given currency by 10^shift, after its minor unit
+// has changed, including those in the journal.
+pub fn rescale_prices(conn: &mut Connection, currency: &str, shift: i32) -> Result<usize> {
+    let tx = conn.transaction()?;
+    let n = rescale(&tx, currency, shift)?;
+    tx.commit()?;
+
+    Ok(n)
+}
+
+fn rescale(tx: &Transaction, currency: &str, shift: i32) -> Result<usize> {
+    // Multiply by ?2 and divide by ?3, one of which is 1, rounding half
+    // away from zero, in integers so that large amounts stay exact
+    let (mul, div) = if shift >= 0 {
+        (10i64.pow(shift.unsigned_abs()), 1i64)
+    } else {
+        (1i64, 10i64.pow(shift.unsigned_abs()))
+    };
+    let scaled = |amount: &str| {
+        format!(
+            "({0} * ?2 + CASE WHEN {0} < 0 THEN -(?3 / 2) ELSE ?3 / 2 END) / ?3",
+            amount
+        )
+    };
+
+    let n = tx.execute(
+        &format!(
+            "UPDATE items SET price = {} WHERE currency = ?1",
+            scaled("price")
+        ),
+        params![currency, mul, div],
+    )?;
+    tx.execute(
+        &format!(
+            "UPDATE item_splits SET price = {}
+                WHERE item_id IN (SELECT id FROM items WHERE currency = ?1)",
+            scaled("price")
+        ),
+        params![currency, mul, div],
+    )?;
+    tx.execute(
+        &format!(
+            "UPDATE item_shares SET amount = {}
+                WHERE amount IS NOT NULL AND item_id IN (SELECT id FROM items WHERE currency = ?1)",
+            scaled("amount")
+        ),
+        params![currency, mul, div],
+    )?;
+    if currency.is_empty() {
+        tx.execute(
+            &format!("UPDATE transfers SET amount = {}", scaled("amount")),
+            params![currency, mul, div],
+        )?;
+    }
+    tx.execute(
+        &format!(
+            "UPDATE receipts SET total = {} WHERE currency = ?1",
+            scaled("total")
+        ),
+        params![currency, mul, div],
+    )?;
+    for column in ["before", "after"] {
+        tx.execute(
+            &format!(
+                "UPDATE journal SET {0} = json_set({0}, '$.price', {1})
+                    WHERE json_extract({0}, '$.currency') = ?1",
+                column,
+                scaled(&format!("json_extract({}, '$.price')", column))
+            ),
+            params![currency, mul, div],
+        )?;
+        tx.execute(
+            &format!(
+                "UPDATE journal SET {0} = json_set({0}, '$.splits', json((
+                        SELECT json_group_array(json_set(value, '$.price', {1}))
+                            FROM json_each({0}, '$.splits'))))
+                    WHERE json_extract({0}, '$.currency') = ?1
+                      AND json_array_length({0}, '$.splits') > 0",
+                column,
+                scaled("json_extract(value, '$.price')")
+            ),
+            params![currency, mul, div],
+        )?;
+        tx.execute(
+            &format!(
+                "UPDATE journal SET {0} = json_set({0}, '$.shares', json((
+                        SELECT json_group_array(json_set(value, '$.amount', {1}))
+                            FROM json_each({0}, '$.shares'))))
+                    WHERE json_extract({0}, '$.currency') = ?1
+                      AND json_array_length({0}, '$.shares') > 0",
+                column,
+                scaled("json_extract(value, '$.amount')")
+            ),
+            params![currency, mul, div],
+        )?;
+    }
+
+    Ok(n)
+}
+
+// Make `new` the home currency.  Prices stored without a code were in the
+// old home currency, so they are given its code, and transfers, which are
+// always in the home currency, take the converted amounts by id.  Where
+// there was no home currency, prices without a code stay as they are,
+// rescaled by 10^shift to the minor units of the new one.
+pub fn set_home_currency(
+    conn: &mut Connection,
+    old: &str,
+    new: &str,
+    shift: i32,
+    transfers: &[(i64, i64)],
+) -> Result<usize> {
+    let tx = conn.transaction()?;
+
+    let n = if old.is_empty() {
+        rescale(&tx, "", shift)?
+    } else {
+        tx.execute(
+            "UPDATE receipts SET currency = ?1 WHERE currency = ''",
+            params![old],
+        )?;
+        for column in ["before", "after"] {
+            tx.execute(
+                &format!(
+                    "UPDATE journal SET {0} = json_set({0}, '$.currency', ?1)
+                        WHERE json_extract({0}, '$.currency') = ''",
+                    column
+                ),
+                params![old],
+            )?;
+        }
+        let mut stmt = tx.prepare("UPDATE transfers SET amount = ?2 WHERE id = ?1")?;
+        for (id, amount) in transfers {
+            stmt.execute(params![id, amount])?;
+        }
+        drop(stmt);
+        tx.execute(
+            "UPDATE items SET currency = ?1 WHERE currency = ''",
+            params![old],
+        )?
+    };
+
+    set_setting(&tx, "home_currency", new)?;
+    tx.commit()?;
+
+    Ok(n)
+}
+
+// Up to `limit` items after `key` in sort order, or before it if not
+// `forward`, always returned in sort order.  Without a key, the first or
+// last items.  Paging by key rather than by offset keeps each page cheap
+// however far into the table it is.
+pub fn select_page(
+    conn: &Connection,
+    sort: SortOrder,
+    filter: &ItemFilter,
+    key: Option<&DbItem>,
+    forward: bool,
+    limit: usize,
+) -> Result<Vec<DbItem>> {
+    let (mut condition, mut params) = filter_condition(filter);
+    if let Some(key) = key {
+        condition += &keyset_clause(sort, forward);
+        params.extend(sort_key(sort, key));
+    }
+    let order = format!("{} LIMIT {}", order_by(sort, forward), limit);
+
+    let mut items = query_items(conn, &condition, &order, params_from_iter(&params))?;
+    if !forward {
+        items.reverse();
+    }
+
+    Ok(items)
+}
+
+// The item, whether or not it is in the trash.
+pub fn select_item(conn: &Connection, id: i64) -> Result<Option<DbItem>> {
+    let items = query_items(conn, "items.id = ?", "items.id", params![id])?;
+
+    Ok(items.into_iter().next())
+}
+
+// The item, if it matches the filter.
+pub fn select_filtered_item(
+    conn: &Connection,
+    filter: &ItemFilter,
+    id: i64,
+) -> Result<Option<DbItem>> {
+    let (condition, mut params) = filter_condition(filter);
+    let condition = condition + " AND items.id = ?";
+    params.push(Value::Integer(id));
+
+    let items = query_items(conn, &condition, "items.id", params_from_iter(&params))?;
+
+    Ok(items.into_iter().next())
+}
+
+// Number of items matching the filter, or only those before `key`.
+pub fn count_items(
+    conn: &Connection,
+    sort: SortOrder,
+    filter: &ItemFilter,
+    key: Option<&DbItem>,
+) -> Result<usize> {
+    let (mut condition, mut params) = filter_condition(filter);
+    if let Some(key) = key {
+        condition += &keyset_clause(sort, false);
+        params.extend(sort_key(sort, key));
+    }
+
+    let sql = format!(
+        "SELECT COUNT(*) FROM items LEFT JOIN stores ON stores.id = items.store_id WHERE {}",
+        condition
+    );
+    conn.query_row(&sql, params_from_iter(&params), |row| row.get(0))
+}
+
+// Amounts of the items matching the filter, summed per day and currency
+// so that each sum can be converted at that day's exchange rate.
+pub fn select_totals(
+    conn: &Connection,
+    filter: &ItemFilter,
+) -> Result<Vec<(String, String, i64, usize)>> {
+    let (condition, params) = filter_condition(filter);
+    let sql = format!(
+        "SELECT date, currency, SUM(price), COUNT(*)
+             FROM items LEFT JOIN stores ON stores.id = items.store_id
+             WHERE {}
+             GROUP BY date, currency",
+        condition
+    );
+
+    let mut stmt = conn.prepare(&sql)?;
+    let totals = stmt
+        .query_map(params_from_iter(&params), |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+        })?
+        .collect();
+
+    totals
+}
+
+// Condition on items and stores matching the filter, with its parameters.
+fn filter_condition(filter: &ItemFilter) -> (String, Vec<Value>) {
+    let mut condition = format!("deleted_at IS NULL {}", tag_filter_clause(&filter.tags));
+    let mut params: Vec<Value> = filter.tags.tags.iter().cloned().map(Value::Text).collect();
+
+    if !filter.search.is_empty() {
+        condition += " AND items.id IN (SELECT rowid FROM items_fts WHERE items_fts MATCH ?)";
+        params.push(Value::Text(filter.search.clone()));
+    }
+
+    if let Some(query) = &filter.query {
+        condition += &format!(" AND ({})", query.sql);
+        params.extend(query.params.iter().cloned());
+    }
+
+    (condition, params)
+}
+
+// Sort order, reversed if not `forward`, with the id breaking ties so that
+// every item has a distinct position.
+fn order_by(sort: SortOrder, forward: bool) -> String {
+    // Column names come from SortColumn, never from user input.
+    let dir = if forward != sort.descending {
+        "ASC"
+    } else {
+        "DESC"
+    };
+    format!("items.{} {}, items.id {}", sort.column.sql(), dir, dir)
+}
+
+// Condition on items after the key in sort order, or before it if not
+// `forward`, bound to the values from sort_key.
+fn keyset_clause(sort: SortOrder, forward: bool) -> String {
+    let op = if forward != sort.descending { ">" } else { "<" };
+    format!(" AND (items.{}, items.id) {} (?, ?)", sort.column.sql(), op)
+}
+
+fn sort_key(sort: SortOrder, item: &DbItem) -> [Value; 2] {
+    let value = match sort.column {
+        SortColumn::Date => Value::Text(item.date.clone()),
+        SortColumn::Category => Value::Text(item.category.clone()),
+        SortColumn::Description => Value::Text(item.description.clone()),
+        SortColumn::Price => Value::Integer(item.price),
+        SortColumn::Id => Value::Integer(item.id),
+    };
+
+    [value, Value::Integer(item.id)]
+}
+
+pub fn select_filters(conn: &Connection) -> Result<BTreeMap<String, String>> {
+    let mut stmt = conn.prepare("SELECT name, query FROM filters")?;
+    let filters = stmt
+        .query_map((), |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect();
+
+    filters
+}
+
+pub fn select_tax_rates(conn: &Connection) -> Result<BTreeMap<String, i64>> {
+    let mut stmt = conn.prepare("SELECT name, rate FROM tax_rates")?;
+    let rates = stmt
+        .query_map((), |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect();
+
+    rates
+}
+
+// Add or change a tax rate.  Without a rate, delete it, so that items and
+// categories with that rate have none.  This is not recorded in the
+// journal and cannot be undone, see tax.rs.
+pub fn set_tax_rate(conn: &mut Connection, name: &str, rate: Option<i64>) -> Result<()> {
+    let tx = conn.transaction()?;
+
+    if let Some(rate) = rate {
+        tx.execute(
+            "INSERT OR REPLACE INTO tax_rates(name, rate) VALUES (?1, ?2)",
+            params![name, rate],
+        )?;
+    } else {
+        tx.execute(
+            "UPDATE items SET tax_rate=NULL WHERE tax_rate=?1",
+            params![name],
+        )?;
+        tx.execute("DELETE FROM category_tax WHERE tax_rate=?1", params![name])?;
+        tx.execute("DELETE FROM tax_rates WHERE name=?1", params![name])?;
+    }
+
+    tx.commit()
+}
+
+pub fn select_category_taxes(conn: &Connection) -> Result<BTreeMap<String, String>> {
+    let mut stmt = conn.prepare("SELECT category, tax_rate FROM category_tax")?;
+    let taxes = stmt
+        .query_map((), |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect();
+
+    taxes
+}
+
+// Set the tax rate that applies to items of a category and its
+// subcategories by default.  An empty rate removes it.  Like the rates
+// themselves, this cannot be undone, unlike the rates that move along with
+// a renamed category.
+pub fn set_category_tax(conn: &Connection, category: &str, tax_rate: &str) -> Result<()> {
+    if tax_rate.is_empty() {
+        conn.execute(
+            "DELETE FROM category_tax WHERE category=?1",
+            params![category],
+        )?;
+    } else {
+        conn.execute(
+            "INSERT OR REPLACE INTO category_tax(category, tax_rate) VALUES (?1, ?2)",
+            params![category, tax_rate],
+        )?;
+    }
+
+    Ok(())
+}
+
+// Save a filter under a name, replacing any filter of that name.  An empty
+// query deletes the saved filter.
+pub fn save_filter(conn: &Connection, name: &str, query: &str) -> Result<()> {
+    if query.is_empty() {
+        conn.execute("DELETE FROM filters WHERE name=?1", params![name])?;
+    } else {
+        conn.execute(
+            "INSERT OR REPLACE INTO filters(name, query) VALUES (?1, ?2)",
+            params![name, query],
+        )?;
+    }
+
+    Ok(())
+}
+
+// Items in the trash, most recently deleted first.
+pub fn select_trash(conn: &Connection) -> Result<Vec<DbItem>> {
+    query_items(
+        conn,
+        "deleted_at IS NOT NULL",
+        "deleted_at DESC, items.id DESC",
+        [],
+    )
+}
+
+fn query_items(
+    conn: &Connection,
+    condition: &str,
+    order: &str,
+    params: impl rusqlite::Params,
+) -> Result<Vec<DbItem>> {
+    let mut stmt = conn.prepare(&items_sql(condition, order))?;
+    let iter = stmt.query_map(params, item_from_row)?;
+
+    iter.collect()
+}
+
+// Hand the items matching the filter, in no particular order, to `f` one
+// at a time as they are read, rather than holding them all in memory.
+pub fn scan_items<T>(
+    conn: &Connection,
+    filter: &ItemFilter,
+    f: impl FnOnce(&mut dyn Iterator<Item = DbItem>) -> T,
+) -> Result<T> {
+    let (condition, params) = filter_condition(filter);
+    let mut stmt = conn.prepare(&items_sql(&condition, "items.id"))?;
+    let rows = stmt.query_map(params_from_iter(&params), item_from_row)?;
+
+    // Stop at the first error, and report it once `f` is done
+    let mut error = None;
+    let result = f(&mut rows.map_while(|item| item.map_err(|err| error = Some(err)).ok()));
+
+    match error {
+        Some(err) => Err(err),
+        None => Ok(result),
+    }
+}
+
+fn items_sql(condition: &str, order: &str) -> String {
+    format!(
+        "SELECT items.id, date, category, description, price, currency, quantity, unit,
+                COALESCE(stores.name, ''), receipt_id,
+                (SELECT GROUP_CONCAT(tags.name, ' ') FROM item_tags
+                    JOIN tags ON tags.id = item_tags.tag_id
+                    WHERE item_tags.item_id = items.id),
+                deleted_at, kind, original_id, tax_rate,
+                (SELECT GROUP_CONCAT(price || ' ' || category, char(10)) FROM (
+                    SELECT price, category FROM item_splits
+                        WHERE item_splits.item_id = items.id
+                        ORDER BY position)),
+                COALESCE(payers.name, ''),
+                (SELECT GROUP_CONCAT(
+                        name || char(9) || COALESCE(percent, '') || char(9) || COALESCE(amount, ''),
+                        char(10)) FROM (
+                    SELECT members.name, percent, amount FROM item_shares
+                        JOIN members ON members.id = item_shares.member_id
+                        WHERE item_shares.item_id = items.id
+                        ORDER BY position))
+            FROM items LEFT JOIN stores ON stores.id = items.store_id
+                LEFT JOIN members AS payers ON payers.id = items.payer_id
+            WHERE {}
+            ORDER BY {}",
+        condition, order
+    )
+}
+
+fn item_from_row(row: &rusqlite::Row) -> Result<DbItem> {
+    let tags: Option<String> = row.get(10)?;
+
+    Ok(DbItem {
+        id: row.get(0)?,
+        date: row.get(1)?,
+        category: row.get(2)?,
+        description: row.get(3)?,
+        price: row.get(4)?,
+        currency: row.get(5)?,
+        quantity: row.get(6)?,
+        unit: row.get(7)?,
+        store: row.get(8)?,
+        receipt_id: row.get(9)?,
+        tags: crate::util::split_tags(&tags.unwrap_or_default()),
+        deleted_at: row.get(11)?,
+        kind: ItemKind::parse(&row.get::<_, String>(12)?).unwrap_or_default(),
+        original_id: row.get(13)?,
+        tax_rate: row.get(14)?,
+        splits: parse_splits(&row.get::<_, Option<String>>(15)?.unwrap_or_default()),
+        payer: row.get(16)?,
+        shares: parse_shares(&row.get::<_, Option<String>>(17)?.unwrap_or_default()),
+    })
+}
+
+// Allocations as selected in query_items, one per line, each the price
+// followed by the category.
+fn parse_splits(text: &str) -> Vec<Split> {
+    text.lines()
+        .filter_map(|line| {
+            let (price, category) = line.split_once(' ')?;
+            Some(Split {
+                category: String::from(category),
+                price: price.parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+// Shares as selected in query_items, one per line, each the member, the
+// percentage and the amount, separated by tabs.
+fn parse_shares(text: &str) -> Vec<Share> {
+    text.lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            Some(Share {
+                member: String::from(fields.next()?),
+                percent: fields.next()?.parse().ok(),
+                amount: fields.next()?.parse().ok(),
+            })
+        })
+        .collect()
+}
+
+// Condition matching items tagged with any or all of the filter's tags,
+// which are bound as parameters ?1, ?2, ...
+fn tag_filter_clause(filter: &TagFilter) -> String {
+    if filter.is_empty() {
+        return String::new();
+    }
+
+    let placeholders = vec!["?"; filter.tags.len()].join(", ");
+    let count = format!(
+        "(SELECT COUNT(*) FROM item_tags JOIN tags ON tags.id = item_tags.tag_id
+            WHERE item_tags.item_id = items.id AND tags.name IN ({}))",
+        placeholders
+    );
+
+    if filter.all {
+        format!("AND {} = {}", count, filter.tags.len())
+    } else {
+        format!("AND {} > 0", count)
+    }
+}
+
+pub fn set_item_tags(conn: &mut Connection, id: i64, tags: &[String]) -> Result<()> {
+    let tx = conn.transaction()?;
+    let mut step = Step::begin(&tx)?;
+    step.track(&tx, id)?;
+
+    tx.execute("DELETE FROM item_tags WHERE item_id=?1", params![id])?;
+
+    for tag in tags {
+        tx.execute("INSERT OR IGNORE INTO tags(name) VALUES (?1)", params![tag])?;
+        tx.execute(
+            "INSERT INTO item_tags(item_id, tag_id)
+                SELECT ?1, id FROM tags WHERE name=?2",
+            params![id, tag],
+        )?;
+    }
+
+    step.finish(&tx)?;
+    tx.commit()
+}
+
+pub fn select_tags(conn: &Connection) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT name FROM tags ORDER BY name")?;
+    let mut rows = stmt.query([])?;
+    let mut tags = Vec::new();
+
+    while let Some(row) = rows.next()? {
+        tags.push(row.get(0)?);
+    }
+
+    Ok(tags)
+}
+
+pub fn select_category(conn: &Connection, description: &str) -> Result<String> {
+    conn.query_row(
+        "SELECT category FROM items WHERE description=?1 AND deleted_at IS NULL LIMIT 1",
+        params![description],
+        |row| row.get(0),
+    )
+}
+
+// Store of the most recent purchase with this description.
+pub fn select_store(conn: &Connection, description: &str) -> Result<String> {
+    conn.query_row(
+        "SELECT stores.name FROM items JOIN stores ON stores.id = items.store_id
+            WHERE description=?1 AND deleted_at IS NULL
+            ORDER BY date DESC, items.id DESC
+            LIMIT 1",
+        params![description],
+        |row| row.get(0),
+    )
+}
+
+pub fn select_members(conn: &Connection) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT name FROM members ORDER BY name")?;
+    let members = stmt.query_map([], |row| row.get(0))?.collect();
+
+    members
+}
+
+// Transfers between members, most recent first.
+pub fn select_transfers(conn: &Connection) -> Result<Vec<Transfer>> {
+    let mut stmt = conn.prepare(
+        "SELECT transfers.id, date, senders.name, receivers.name, amount FROM transfers
+            JOIN members AS senders ON senders.id = transfers.from_id
+            JOIN members AS receivers ON receivers.id = transfers.to_id
+            ORDER BY date DESC, transfers.id DESC",
+    )?;
+    let transfers = stmt
+        .query_map([], |row| {
+            Ok(Transfer {
+                id: row.get(0)?,
+                date: row.get(1)?,
+                from: row.get(2)?,
+                to: row.get(3)?,
+                amount: row.get(4)?,
+            })
+        })?
+        .collect();
+
+    transfers
+}
+
+pub fn insert_transfers(conn: &mut Connection, transfers: &[Transfer]) -> Result<()> {
+    let tx = conn.transaction()?;
+
+    for transfer in transfers {
+        let from_id = member_id(&tx, &transfer.from)?;
+        let to_id = member_id(&tx, &transfer.to)?;
+        tx.execute(
+            "INSERT INTO transfers(date, from_id, to_id, amount) VALUES (?1, ?2, ?3, ?4)",
+            params![transfer.date, from_id, to_id, transfer.amount],
+        )?;
+    }
+
+    tx.commit()
+}
+
+pub fn delete_transfer(conn: &Connection, id: i64) -> Result<()> {
+    conn.execute("DELETE FROM transfers WHERE id=?1", params![id])?;
+
+    Ok(())
+}
+
+pub fn select_stores(conn: &Connection) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT name FROM stores ORDER BY name")?;
+    let mut rows = stmt.query([])?;
+    let mut stores = Vec::new();
+
+    while let Some(row) = rows.next()? {
+        stores.push(row.get(0)?);
+    }
+
+    Ok(stores)
+}
+
+// Rename a category and all of its subcategories, e.g. "Food" to
+// "Groceries" also turns "Food:Dairy" into "Groceries:Dairy".
+pub fn rename_category(
+    conn: &mut Connection,
+    old: &str,
+    new: &str,
+    separator: &str,
+) -> Result<usize> {
+    let tx = conn.transaction()?;
+    let ids = select_ids(
+        &tx,
+        "SELECT id FROM items
+            WHERE category = ?1
+               OR substr(category, 1, length(?1) + length(?2)) = ?1 || ?2
+         UNION
+         SELECT item_id FROM item_splits
+            WHERE category = ?1
+               OR substr(category, 1, length(?1) + length(?2)) = ?1 || ?2",
+        params![old, separator],
+    )?;
+
+    let mut step = Step::begin(&tx)?;
+
+    // Tax rates of the categories move with them, replacing any the new
+    // names had
+    let taxed: Vec<String> = tx
+        .prepare(
+            "SELECT category FROM category_tax
+                WHERE category = ?1
+                   OR substr(category, 1, length(?1) + length(?2)) = ?1 || ?2",
+        )?
+        .query_map(params![old, separator], |row| row.get(0))?
+        .collect::<Result<_>>()?;
+    for category in &taxed {
+        step.track_category_tax(&tx, category)?;
+        step.track_category_tax(&tx, &format!("{}{}", new, &category[old.len()..]))?;
+    }
+
+    for &id in &ids {
+        step.track(&tx, id)?;
+        tx.execute(
+            "UPDATE items SET category = ?2 || substr(category, length(?1) + 1)
+                WHERE id=?3
+                  AND (category = ?1
+                       OR substr(category, 1, length(?1) + length(?4)) = ?1 || ?4)",
+            params![old, new, id, separator],
+        )?;
+        tx.execute(
+            "UPDATE item_splits SET category = ?2 || substr(category, length(?1) + 1)
+                WHERE item_id=?3
+                  AND (category = ?1
+                       OR substr(category, 1, length(?1) + length(?4)) = ?1 || ?4)",
+            params![old, new, id, separator],
+        )?;
+    }
+
+    tx.execute(
+        "UPDATE OR REPLACE category_tax SET category = ?2 || substr(category, length(?1) + 1)
+            WHERE category = ?1
+               OR substr(category, 1, length(?1) + length(?3)) = ?1 || ?3",
+        params![old, new, separator],
+    )?;
+
+    step.finish(&tx)?;
+    tx.commit()?;
+
+    Ok(ids.len())
+}
+
+// Move all items of the given categories into one, which may be new.
+pub fn merge_categories(conn: &mut Connection, categories: &[String], into: &str) -> Result<usize> {
+    let tx = conn.transaction()?;
+    let mut step = Step::begin(&tx)?;
+    let mut count = 0;
+
+    for category in categories {
+        let ids = select_ids(
+            &tx,
+            "SELECT id FROM items WHERE category=?1
+             UNION
+             SELECT item_id FROM item_splits WHERE category=?1",
+            params![category],
+        )?;
+
+        for &id in &ids {
+            step.track(&tx, id)?;
+            tx.execute(
+                "UPDATE items SET category=?3 WHERE id=?1 AND category=?2",
+                params![id, category, into],
+            )?;
+            tx.execute(
+                "UPDATE item_splits SET category=?3 WHERE item_id=?1 AND category=?2",
+                params![id, category, into],
+            )?;
+        }
+        count += ids.len();
+    }
+
+    step.finish(&tx)?;
+    tx.commit()?;
+
+    Ok(count)
+}
+
+fn select_ids(conn: &Connection, sql: &str, params: impl rusqlite::Params) -> Result<Vec<i64>> {
+    let mut stmt = conn.prepare(sql)?;
+    let ids = stmt.query_map(params, |row| row.get(0))?.collect();
+
+    ids
+}
+
+pub fn select_categories(conn: &Connection) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT category FROM items WHERE deleted_at IS NULL
+         UNION
+         SELECT item_splits.category FROM item_splits JOIN items ON items.id = item_splits.item_id
+            WHERE deleted_at IS NULL
+         ORDER BY category",
+    )?;
+    let mut rows = stmt.query([])?;
+    let mut categories = Vec::new();
+
+    while let Some(row) = rows.next()? {
+        categories.push(row.get(0)?);
+    }
+
+    Ok(categories)
+}
+
+pub fn select_descriptions(conn: &Connection) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT DISTINCT description FROM items WHERE deleted_at IS NULL ORDER BY description",
+    )?;
+    let mut rows = stmt.query([])?;
+    let mut descriptions = Vec::new();
+
+    while let Some(row) = rows.next()? {
+        descriptions.push(row.get(0)?);
+    }
+
+    Ok(descriptions)
+}
+
+// Whether any item outside the trash has the value in the column, which
+// comes from the caller, never from user input.  A category is also used
+// by the allocations of split items.
+pub fn is_used(conn: &Connection, column: &str, value: &str) -> Result<bool> {
+    let mut sql = format!(
+        "SELECT EXISTS (SELECT 1 FROM items WHERE {}=?1 AND deleted_at IS NULL)",
+        column
+    );
+    if column == "category" {
+        sql += " OR EXISTS (SELECT 1 FROM item_splits JOIN items ON items.id = item_splits.item_id
+                    WHERE item_splits.category=?1 AND deleted_at IS NULL)";
+    }
+
+    conn.query_row(&sql, params![value], |row| row.get(0))
+}
+
+// Shrink the database file after many deletions, merging the full-text
+// index first so that it takes less space too.
+pub fn compact(conn: &Connection) -> Result<()> {
+    conn.execute("INSERT INTO items_fts(items_fts) VALUES ('optimize')", ())?;
+    conn.execute_batch("VACUUM")
+}
+
+// Counter that changes whenever another connection commits a change.
+pub fn data_version(conn: &Connection) -> Result<i64> {
+    conn.query_row("PRAGMA data_version", (), |row| row.get(0))
+}
+
+pub fn insert_rates(conn: &mut Connection, rates: &[Rate], progress: Progress) -> Result<()> {
+    let tx = conn.transaction()?;
+
+    {
+        let mut stmt = tx.prepare(
+            "INSERT OR REPLACE INTO exchange_rates(date, from_currency, to_currency, rate)
+                VALUES (?1, ?2, ?3, ?4)",
+        )?;
+
+        for (n, rate) in rates.iter().enumerate() {
+            if !progress(n, rates.len()) {
+                return Err(cancelled());
+            }
+            stmt.execute(params![rate.date, rate.from, rate.to, rate.rate])?;
+        }
+    }
+
+    tx.commit()
+}
+
+pub fn select_rates(conn: &Connection) -> Result<Vec<Rate>> {
+    let mut stmt =
+        conn.prepare("SELECT date, from_currency, to_currency, rate FROM exchange_rates")?;
+    let iter = stmt.query_map([], |row| {
+        Ok(Rate {
+            date: row.get(0)?,
+            from: row.get(1)?,
+            to: row.get(2)?,
+            rate: row.get(3)?,
+        })
+    })?;
+
+    iter.collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::App;
+    use crate::journal;
+
+    fn price(conn: &Connection, id: i64) -> i64 {
+        select_item(conn, id).unwrap().unwrap().price
+    }
+
+    fn journal_prices(conn: &Connection, id: i64) -> Vec<String> {
+        let mut stmt = conn
+            .prepare(
+                "SELECT json_extract(after, '$.price') || ' ' || json_extract(after, '$.splits')
+                    FROM journal WHERE item_id = ?1 AND after IS NOT NULL ORDER BY id",
+            )
+            .unwrap();
+        stmt.query_map(params![id], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn minor_units_migration() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        for sql in &MIGRATIONS[..12] {
+            conn.execute_batch(sql).unwrap();
+        }
+        conn.pragma_update(None, "user_version", 12).unwrap();
+        conn.execute_batch(
+            "INSERT INTO settings VALUES ('home_currency', 'JPY');
+             INSERT INTO items(id, date, category, description, price, currency) VALUES
+                 (1, '2026-10-01', 'Food', 'Tea', 123450, 'JPY'),
+                 (2, '2026-10-01', 'Food', 'Jam', -150, ''),
+                 (3, '2026-10-01', 'Food', 'Egg', 1235, 'BHD'),
+                 (4, '2026-10-01', 'Food', 'Oil', 1801439850948199, 'KWD'),
+                 (5, '2026-10-01', 'Food', 'Rye', 1999, 'EUR');
+             INSERT INTO journal(step, item_id, before, after) VALUES
+                 (1, 1, NULL, '{\"price\":123450,\"currency\":\"JPY\"}'),
+                 (2, 3, '{\"price\":1235,\"currency\":\"BHD\"}', '{\"price\":-149,\"currency\":\"\"}');",
+        )
+        .unwrap();
+        migrate(&mut conn).unwrap();
+
+        let prices: Vec<i64> = (1..=5).map(|id| price(&conn, id)).collect();
+        assert_eq!(prices, [1235, -2, 12350, 18014398509481990, 1999]);
+
+        let images: Vec<(Option<i64>, i64)> = conn
+            .prepare(
+                "SELECT json_extract(before, '$.price'), json_extract(after, '$.price')
+                    FROM journal ORDER BY id",
+            )
+            .unwrap()
+            .query_map((), |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(images, [(None, 1235), (Some(12350), -1)]);
+    }
+
+    #[test]
+    fn rescale_is_exact() {
+        let mut conn = open(std::path::Path::new(":memory:")).unwrap();
+        let mut item = App::new().new_item;
+        item.date = String::from("2026-10-01");
+        item.description = String::from("Supermarket");
+        item.currency = String::from("ISK");
+        item.price = 90071992547409931;
+        item.splits = vec![
+            Split {
+                category: String::from("Food"),
+                price: 90071992547409930,
+            },
+            Split {
+                category: String::from("Pet"),
+                price: 1,
+            },
+        ];
+        let big = insert_item(&conn, &item).unwrap();
+
+        item.price = -1235;
+        item.splits = Vec::new();
+        let refund = insert_item(&conn, &item).unwrap();
+
+        // More minor units, then fewer, rounding half away from zero
+        assert_eq!(rescale_prices(&mut conn, "ISK", 1).unwrap(), 2);
+        assert_eq!(price(&conn, big), 900719925474099310);
+        assert_eq!(
+            journal_prices(&conn, big),
+            [
+                r#"900719925474099310 [{"category":"Food","price":900719925474099300},{"category":"Pet","price":10}]"#
+            ]
+        );
+
+        rescale_prices(&mut conn, "ISK", -2).unwrap();
+        let restored = select_item(&conn, big).unwrap().unwrap();
+        assert_eq!(restored.price, 9007199254740993);
+        assert_eq!(restored.splits[0].price, 9007199254740993);
+        assert_eq!(restored.splits[1].price, 0);
+        assert_eq!(price(&conn, refund), -124);
+        assert_eq!(journal_prices(&conn, refund), ["-124 []"]);
+
+        // Undoing and redoing the insert brings back the rescaled image
+        journal::undo(&mut conn).unwrap();
+        journal::undo(&mut conn).unwrap();
+        journal::redo(&mut conn).unwrap();
+        assert_eq!(
+            select_item(&conn, big).unwrap().unwrap().splits,
+            restored.splits
+        );
+    }
+
+    #[test]
+    fn home_currency_change() {
+        let mut conn = open(std::path::Path::new(":memory:")).unwrap();
+        let mut item = App::new().new_item;
+        item.date = String::from("2026-10-01");
+        item.description = String::from("Tea");
+        item.price = 12345;
+        let tea = insert_item(&conn, &item).unwrap();
+        insert_transfers(
+            &mut conn,
+            &[Transfer {
+                id: 0,
+                date: String::from("2026-10-02"),
+                from: String::from("Ann"),
+                to: String::from("Bob"),
+                amount: 5050,
+            }],
+        )
+        .unwrap();
+
+        // Without a home currency, unmarked prices take the new minor units
+        set_home_currency(&mut conn, "", "JPY", -2, &[]).unwrap();
+        assert_eq!(price(&conn, tea), 123);
+        assert_eq!(journal_prices(&conn, tea), ["123 []"]);
+        assert_eq!(select_transfers(&conn).unwrap()[0].amount, 51);
+
+        // With one, they keep their amounts under its code, and transfers
+        // take the converted amounts
+        let id = select_transfers(&conn).unwrap()[0].id;
+        set_home_currency(&mut conn, "JPY", "EUR", 2, &[(id, 31)]).unwrap();
+        let tea = select_item(&conn, tea).unwrap().unwrap();
+        assert_eq!((tea.price, tea.currency.as_str()), (123, "JPY"));
+        assert_eq!(select_transfers(&conn).unwrap()[0].amount, 31);
+        assert_eq!(
+            get_setting(&conn, "home_currency").unwrap().as_deref(),
+            Some("EUR")
+        );
+    }
+}
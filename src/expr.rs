@@ -0,0 +1,280 @@
+use crate::currency::MAX_EXPONENT;
+use crate::locale::Locale;
+
+// Amounts typed as arithmetic, e.g. "3x1.29", "4.99 - 0.50" or
+// "(2.49 + 1.99) / 2", with + - * / and parentheses, where "x" and "×"
+// also multiply.  Numbers are read as written in the locale.
+//
+// Everything is worked out exactly, as fractions of integers, and only the
+// result is rounded: to the nearest minor unit of the currency, with
+// halves rounded away from zero, so "10/3" is 3.33 and "0.125*1" is 0.13.
+pub fn evaluate(locale: &Locale, text: &str, exponent: u32) -> Option<i64> {
+    let mut parser = Parser {
+        tokens: tokenize(locale, text)?,
+        pos: 0,
+    };
+
+    let value = parser.sum()?;
+    if parser.pos < parser.tokens.len() {
+        return None;
+    }
+
+    value.round(exponent)
+}
+
+// Fraction in lowest terms, with a positive denominator.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Fraction {
+    num: i128,
+    den: i128,
+}
+
+impl Fraction {
+    fn new(num: i128, den: i128) -> Option<Fraction> {
+        // Keeping clear of i128::MIN means signs can always be flipped
+        if den == 0 || num == i128::MIN || den == i128::MIN {
+            return None;
+        }
+        let gcd = gcd(num, den);
+        let sign = den.signum();
+        Some(Fraction {
+            num: sign * num / gcd,
+            den: sign * den / gcd,
+        })
+    }
+
+    fn add(self, other: Fraction) -> Option<Fraction> {
+        let num = self
+            .num
+            .checked_mul(other.den)?
+            .checked_add(other.num.checked_mul(self.den)?)?;
+        Fraction::new(num, self.den.checked_mul(other.den)?)
+    }
+
+    fn mul(self, other: Fraction) -> Option<Fraction> {
+        Fraction::new(
+            self.num.checked_mul(other.num)?,
+            self.den.checked_mul(other.den)?,
+        )
+    }
+
+    fn neg(self) -> Fraction {
+        Fraction {
+            num: -self.num,
+            den: self.den,
+        }
+    }
+
+    fn recip(self) -> Option<Fraction> {
+        Fraction::new(self.den, self.num)
+    }
+
+    // Nearest whole number of minor units, halves away from zero.
+    fn round(self, exponent: u32) -> Option<i64> {
+        let scaled = self.num.checked_mul(10i128.pow(exponent))?;
+        let rounded = scaled
+            .checked_abs()?
+            .checked_mul(2)?
+            .checked_add(self.den)?
+            / self.den.checked_mul(2)?;
+        i64::try_from(scaled.signum() * rounded).ok()
+    }
+}
+
+fn gcd(a: i128, b: i128) -> i128 {
+    let (mut a, mut b) = (a.abs(), b.abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a.max(1)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Token {
+    Number(Fraction),
+    Op(char),
+    Open,
+    Close,
+}
+
+fn tokenize(locale: &Locale, text: &str) -> Option<Vec<Token>> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        if c.is_ascii_digit() {
+            // Separators belong to the number only between digits, so
+            // that "1 234" is one number where spaces group thousands,
+            // but "1 + 2" is not
+            let start = i;
+            while i < chars.len()
+                && (chars[i].is_ascii_digit()
+                    || locale.is_number_char(chars[i])
+                        && chars.get(i + 1).is_some_and(char::is_ascii_digit))
+            {
+                i += 1;
+            }
+            let number: String = chars[start..i].iter().collect();
+            let minor = locale.parse_price(&number, MAX_EXPONENT)?;
+            tokens.push(Token::Number(Fraction::new(
+                minor.into(),
+                10i128.pow(MAX_EXPONENT),
+            )?));
+            continue;
+        }
+
+        match c {
+            '+' | '-' | '*' | '/' => tokens.push(Token::Op(c)),
+            'x' | 'X' | '×' => tokens.push(Token::Op('*')),
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            _ if c.is_whitespace() => (),
+            _ => return None,
+        }
+        i += 1;
+    }
+
+    Some(tokens)
+}
+
+// Recursive descent over the grammar
+//
+//   sum     = product { ("+" | "-") product }
+//   product = unary { ("*" | "/") unary }
+//   unary   = ("+" | "-") unary | number | "(" sum ")"
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).copied();
+        self.pos += 1;
+        token
+    }
+
+    fn next_op(&mut self, ops: &[char]) -> Option<char> {
+        match self.tokens.get(self.pos) {
+            Some(&Token::Op(op)) if ops.contains(&op) => {
+                self.pos += 1;
+                Some(op)
+            }
+            _ => None,
+        }
+    }
+
+    fn sum(&mut self) -> Option<Fraction> {
+        let mut value = self.product()?;
+        while let Some(op) = self.next_op(&['+', '-']) {
+            let rhs = self.product()?;
+            value = value.add(if op == '-' { rhs.neg() } else { rhs })?;
+        }
+        Some(value)
+    }
+
+    fn product(&mut self) -> Option<Fraction> {
+        let mut value = self.unary()?;
+        while let Some(op) = self.next_op(&['*', '/']) {
+            let rhs = self.unary()?;
+            value = value.mul(if op == '/' { rhs.recip()? } else { rhs })?;
+        }
+        Some(value)
+    }
+
+    fn unary(&mut self) -> Option<Fraction> {
+        match self.next()? {
+            Token::Op('-') => Some(self.unary()?.neg()),
+            Token::Op('+') => self.unary(),
+            Token::Number(value) => Some(value),
+            Token::Open => {
+                let value = self.sum()?;
+                (self.next()? == Token::Close).then_some(value)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::App;
+
+    fn eval(text: &str) -> Option<i64> {
+        evaluate(&Locale::default(), text, 2)
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(eval("3x1.29"), Some(387));
+        assert_eq!(eval("3 × 1.29"), Some(387));
+        assert_eq!(eval("4.99 - 0.50"), Some(449));
+        assert_eq!(eval("(2.49 + 1.99) / 2"), Some(224));
+        assert_eq!(eval("-2 * -1.5"), Some(300));
+        assert_eq!(eval("1 - 2 - 3"), Some(-400));
+    }
+
+    #[test]
+    fn rounding() {
+        assert_eq!(eval("10/3"), Some(333));
+        assert_eq!(eval("0.125*1"), Some(13));
+        assert_eq!(eval("-0.125*1"), Some(-13));
+        assert_eq!(evaluate(&Locale::default(), "2.5", 0), Some(3));
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(eval(""), None);
+        assert_eq!(eval("1 +"), None);
+        assert_eq!(eval("(1 + 2"), None);
+        assert_eq!(eval("1 / 0"), None);
+        assert_eq!(eval("1 / (2 - 2)"), None);
+        assert_eq!(eval("1.2.3"), None);
+    }
+
+    #[test]
+    fn overflow() {
+        assert_eq!(eval("100000000000000.0001*100000000000000.0001"), None);
+        assert_eq!(eval("99999999999999999999999999"), None);
+        assert_eq!(eval("1/3*1/3*1/3*1/3*1/3*1/3*1/3*1/3*1/3*1/3"), Some(0));
+        assert_eq!(eval("9223372036854775807 * 10"), None);
+    }
+
+    #[test]
+    fn note_after_amount() {
+        let mut app = App::new();
+        app.rates = crate::currency::Rates::new(vec![crate::currency::Rate {
+            date: String::from("2026-10-01"),
+            from: String::from("USD"),
+            to: String::from("EUR"),
+            rate: 0.92,
+        }]);
+        assert_eq!(
+            app.parse_money("4.99 - 0.50 coupon"),
+            Some((449, String::new()))
+        );
+        assert_eq!(
+            app.parse_money("4.99 - 0.50 off"),
+            Some((449, String::new()))
+        );
+        assert_eq!(app.parse_money("3x1.29 for"), Some((387, String::new())));
+        assert_eq!(app.parse_money("12.50 USX"), Some((1250, String::new())));
+        assert_eq!(
+            app.parse_money("12.50 usd"),
+            Some((1250, String::from("USD")))
+        );
+        assert_eq!(
+            app.parse_money("12 USD two for one"),
+            Some((1200, String::from("USD")))
+        );
+        assert_eq!(
+            app.parse_money("1000 JPY"),
+            Some((1000, String::from("JPY")))
+        );
+        assert_eq!(app.parse_money("coupon"), None);
+    }
+}
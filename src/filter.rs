@@ -0,0 +1,421 @@
+use crate::app::{App, ItemKind};
+use crate::currency::MAX_EXPONENT;
+use chrono::naive::NaiveDate;
+use chrono::{Days, Months};
+use rusqlite::types::Value;
+
+// Filter typed in Browse mode, e.g. "category:Food date:2026-09 price>20",
+// compiled to a condition on the items table.  Terms are combined with AND:
+//
+//   category:Food     category Food or any of its subcategories
+//   desc:coffee       description containing "coffee", or just "coffee"
+//   desc=Coffee       description exactly "Coffee"
+//   store:Aldi        bought at store Aldi
+//   tag:gift          tagged "gift"
+//   currency:USD      priced in US dollars
+//   kind:refund       refunds; also purchase, discount
+//   date:2026-09      in September 2026; also a year or a single day
+//   date>=2026-09     on or after 1 September 2026; also >, <, <=
+//   since:-30d        in the last 30 days; also -2w, -3m, -1y, today
+//   until:2026-09-18  on or before a day
+//   price>20          more than 20 in the item's currency; also >=, <, <=, =
+//   @name             the saved filter called name
+//
+// Any term may be negated with a leading "-", and values containing
+// spaces may be quoted, e.g. desc:"olive oil".
+#[derive(Clone, Debug, PartialEq)]
+pub struct Query {
+    // Filter as typed.
+    pub text: String,
+
+    // Condition with anonymous parameters, and the values to bind.
+    pub sql: String,
+    pub params: Vec<Value>,
+}
+
+// Saved filters may refer to each other, but not without end.
+const MAX_DEPTH: usize = 8;
+
+pub fn parse(app: &App, line: &str) -> Result<Query, String> {
+    let mut params = Vec::new();
+    let sql = compile(app, line, &mut params, 0)?;
+
+    Ok(Query {
+        text: String::from(line.trim()),
+        sql,
+        params,
+    })
+}
+
+fn compile(app: &App, line: &str, params: &mut Vec<Value>, depth: usize) -> Result<String, String> {
+    let mut conditions = Vec::new();
+
+    for token in tokenize(line)? {
+        let (negated, term) = match token.strip_prefix('-') {
+            Some(term) if !term.is_empty() => (true, term),
+            _ => (false, token.as_str()),
+        };
+
+        let condition = if let Some(name) = term.strip_prefix('@') {
+            let saved = app
+                .saved_filters
+                .get(name)
+                .ok_or_else(|| format!("no saved filter @{}", name))?;
+            if depth >= MAX_DEPTH {
+                return Err(format!("saved filter @{} refers to itself", name));
+            }
+            compile(app, saved, params, depth + 1)?
+        } else {
+            compile_term(app, term, params)?
+        };
+
+        if negated {
+            conditions.push(format!("NOT ({})", condition));
+        } else {
+            conditions.push(format!("({})", condition));
+        }
+    }
+
+    if conditions.is_empty() {
+        Ok(String::from("1"))
+    } else {
+        Ok(conditions.join(" AND "))
+    }
+}
+
+// Split on whitespace, except inside double quotes, which are removed.
+fn tokenize(line: &str) -> Result<Vec<String>, String> {
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    let mut quoted = false;
+
+    for c in line.chars() {
+        if c == '"' {
+            quoted = !quoted;
+        } else if c.is_whitespace() && !quoted {
+            if !token.is_empty() {
+                tokens.push(std::mem::take(&mut token));
+            }
+        } else {
+            token.push(c);
+        }
+    }
+
+    if quoted {
+        return Err(String::from("unterminated quote"));
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+// Split "price>=20" into ("price", ">=", "20").  Anything without a field
+// name and operator is searched for in descriptions.
+fn split_term(term: &str) -> (&str, &str, &str) {
+    let field_len = term
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or(term.len());
+    let (field, rest) = term.split_at(field_len);
+
+    for op in [">=", "<=", ">", "<", "=", ":"] {
+        if let Some(value) = rest.strip_prefix(op) {
+            if !field.is_empty() {
+                return (field, op, value);
+            }
+        }
+    }
+
+    ("desc", ":", term)
+}
+
+fn compile_term(app: &App, term: &str, params: &mut Vec<Value>) -> Result<String, String> {
+    let (field, op, value) = split_term(term);
+    let is_match = op == ":" || op == "=";
+
+    match field.to_ascii_lowercase().as_str() {
+        "category" | "cat" if is_match => {
+            // Include subcategories, e.g. "Food" matches "Food:Dairy"
+            let prefix = format!("{}{}", value, app.category_separator);
+            let sql = format!(
+                "category = ? COLLATE NOCASE OR substr(category, 1, {}) = ? COLLATE NOCASE",
+                prefix.chars().count()
+            );
+            params.push(Value::Text(String::from(value)));
+            params.push(Value::Text(prefix));
+            Ok(sql)
+        }
+        "desc" | "description" if op == ":" => {
+            params.push(Value::Text(format!("%{}%", escape_like(value))));
+            Ok(String::from("description LIKE ? ESCAPE '\\'"))
+        }
+        "desc" | "description" if op == "=" => {
+            params.push(Value::Text(String::from(value)));
+            Ok(String::from("description = ? COLLATE NOCASE"))
+        }
+        "store" | "shop" if is_match => {
+            params.push(Value::Text(String::from(value)));
+            // Items with no store have no name; compare "" instead of NULL
+            // so that -store:X keeps them.
+            Ok(String::from("COALESCE(stores.name, '') = ? COLLATE NOCASE"))
+        }
+        "tag" if is_match => {
+            params.push(Value::Text(String::from(value)));
+            Ok(String::from(
+                "EXISTS (SELECT 1 FROM item_tags JOIN tags ON tags.id = item_tags.tag_id
+                     WHERE item_tags.item_id = items.id AND tags.name = ?)",
+            ))
+        }
+        "currency" if is_match => {
+            let code = crate::currency::parse_code(value)
+                .ok_or_else(|| format!("invalid currency {}", value))?;
+            // Items in the home currency are mostly stored without a code,
+            // but may have it if it was typed
+            let sql = if code == app.home_currency {
+                "currency IN ('', ?)"
+            } else {
+                "currency = ?"
+            };
+            params.push(Value::Text(code));
+            Ok(String::from(sql))
+        }
+        "kind" if is_match => {
+            let kind = ItemKind::parse(&value.to_ascii_lowercase())
+                .ok_or_else(|| format!("invalid kind {}", value))?;
+            params.push(Value::Text(String::from(kind.sql())));
+            Ok(String::from("kind = ?"))
+        }
+        "date" => compile_date(op, value, params),
+        "since" if is_match => compile_date(">=", value, params),
+        "until" if is_match => compile_date("<=", value, params),
+        "price" => {
+            // Currencies differ in their minor units, so compare prices
+            // scaled to the most digits any currency may have
+            let price = app
+                .locale
+                .parse_price(value, MAX_EXPONENT)
+                .ok_or_else(|| format!("invalid price {}", value))?;
+            let mut exponents = app.minor_units.list();
+            exponents.insert(String::new(), app.exponent(""));
+
+            let mut scale = String::from("CASE currency");
+            for (code, exponent) in exponents.into_iter().filter(|&(_, e)| e != 2) {
+                scale += " WHEN ? THEN ?";
+                params.push(Value::Text(code));
+                params.push(Value::Integer(10i64.pow(MAX_EXPONENT - exponent)));
+            }
+            scale += " ELSE ? END";
+            params.push(Value::Integer(10i64.pow(MAX_EXPONENT - 2)));
+
+            let op = if op == ":" { "=" } else { op };
+            params.push(Value::Integer(price));
+            Ok(format!("price * {} {} ?", scale, op))
+        }
+        "category" | "cat" | "desc" | "description" | "store" | "shop" | "tag" | "currency"
+        | "kind" | "since" | "until" => Err(format!("{} cannot be compared with {}", field, op)),
+        _ => Err(format!("unknown field {}", field)),
+    }
+}
+
+// Compare dates as the range of days they cover, e.g. "2026-09" covers
+// 2026-09-01 up to but not including 2026-10-01.
+fn compile_date(op: &str, value: &str, params: &mut Vec<Value>) -> Result<String, String> {
+    let (start, end) = date_range(value).ok_or_else(|| format!("invalid date {}", value))?;
+
+    let sql = match op {
+        ":" | "=" => {
+            params.push(Value::Text(start));
+            params.push(Value::Text(end));
+            return Ok(String::from("date >= ? AND date < ?"));
+        }
+        ">" => {
+            params.push(Value::Text(end));
+            "date >= ?"
+        }
+        ">=" => {
+            params.push(Value::Text(start));
+            "date >= ?"
+        }
+        "<" => {
+            params.push(Value::Text(start));
+            "date < ?"
+        }
+        _ => {
+            params.push(Value::Text(end));
+            "date < ?"
+        }
+    };
+
+    Ok(String::from(sql))
+}
+
+// First day and the day after the last, as strings comparable with dates
+// in the items table.
+fn date_range(value: &str) -> Option<(String, String)> {
+    let today = crate::util::parse_date(&crate::util::today())?;
+    let day = |date: NaiveDate| {
+        let next = date.checked_add_days(Days::new(1))?;
+        Some((date.format("%F").to_string(), next.format("%F").to_string()))
+    };
+
+    if value == "today" {
+        return day(today);
+    }
+
+    // Relative to today, e.g. "-30d"
+    if let Some(rest) = value.strip_prefix('-') {
+        let (i, unit) = rest.char_indices().last()?;
+        let n: u32 = rest[..i].parse().ok()?;
+        let date = match unit {
+            'd' => today.checked_sub_days(Days::new(n.into()))?,
+            'w' => today.checked_sub_days(Days::new(7 * u64::from(n)))?,
+            'm' => today.checked_sub_months(Months::new(n))?,
+            'y' => today.checked_sub_months(Months::new(n.checked_mul(12)?))?,
+            _ => return None,
+        };
+        return day(date);
+    }
+
+    let parts: Vec<&str> = value.split('-').collect();
+    match parts[..] {
+        [year] if year.len() == 4 => {
+            let year: i32 = year.parse().ok()?;
+            Some((format!("{:04}", year), format!("{:04}", year + 1)))
+        }
+        [year, month] if year.len() == 4 && month.len() == 2 => {
+            let first = NaiveDate::parse_from_str(&format!("{}-01", value), "%F").ok()?;
+            let next = first.checked_add_months(Months::new(1))?;
+            Some((
+                first.format("%Y-%m").to_string(),
+                next.format("%Y-%m").to_string(),
+            ))
+        }
+        _ => day(crate::util::parse_date(value)?),
+    }
+}
+
+fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+
+    fn items(app: &App, conn: &rusqlite::Connection, line: &str) -> Vec<String> {
+        let mut filter = app.filter.clone();
+        filter.query = Some(parse(app, line).unwrap());
+        db::scan_items(conn, &filter, |items| {
+            items.map(|item| item.description).collect()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn terms() {
+        let app = App::new();
+        let query = parse(&app, "category:Food -desc:\"olive oil\" price>20").unwrap();
+        assert_eq!(query.text, "category:Food -desc:\"olive oil\" price>20");
+        assert!(query.sql.contains("NOT (description LIKE ?"));
+        assert_eq!(query.params[2], Value::Text(String::from("%olive oil%")));
+
+        assert_eq!(parse(&app, "").unwrap().sql, "1");
+        assert!(parse(&app, "colour:red").is_err());
+        assert!(parse(&app, "store>Aldi").is_err());
+        assert!(parse(&app, "desc:\"olive").is_err());
+        assert!(parse(&app, "price>abc").is_err());
+    }
+
+    #[test]
+    fn saved_filters() {
+        let mut app = App::new();
+        app.saved_filters
+            .insert(String::from("food"), String::from("category:Food"));
+        app.saved_filters
+            .insert(String::from("loop"), String::from("@loop"));
+
+        assert_eq!(parse(&app, "@food").unwrap().params.len(), 2);
+        assert!(parse(&app, "@missing").is_err());
+        assert!(parse(&app, "@loop").is_err());
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(
+            date_range("2026"),
+            Some((String::from("2026"), String::from("2027")))
+        );
+        assert_eq!(
+            date_range("2026-12"),
+            Some((String::from("2026-12"), String::from("2027-01")))
+        );
+        assert_eq!(
+            date_range("2026-02-28"),
+            Some((String::from("2026-02-28"), String::from("2026-03-01")))
+        );
+        assert!(date_range("-30d").is_some());
+        assert!(date_range("-2w").is_some());
+        assert!(date_range("-3m").is_some());
+        assert!(date_range("-1y").is_some());
+        assert!(date_range("today").is_some());
+
+        assert_eq!(date_range("2026-13"), None);
+        assert_eq!(date_range("-"), None);
+        assert_eq!(date_range("-d"), None);
+        assert_eq!(date_range("-30x"), None);
+    }
+
+    #[test]
+    fn dates_out_of_range() {
+        let app = App::new();
+        assert!(parse(&app, "since:-€").is_err());
+        assert!(parse(&app, "since:-3€").is_err());
+        assert!(parse(&app, "since:-é1").is_err());
+        assert!(parse(&app, "since:-400000000y").is_err());
+        assert!(parse(&app, "since:-4294967295d").is_err());
+        assert!(parse(&app, "until:-99999999999999999999d").is_err());
+    }
+
+    #[test]
+    fn home_currency() {
+        let conn = db::open(std::path::Path::new(":memory:")).unwrap();
+        let mut app = App::new();
+        app.home_currency = String::from("EUR");
+
+        for (description, currency) in [("Tea", ""), ("Jam", "EUR"), ("Egg", "USD")] {
+            let mut item = app.new_item.clone();
+            item.date = String::from("2026-10-01");
+            item.description = String::from(description);
+            item.currency = String::from(currency);
+            db::insert_item(&conn, &item).unwrap();
+        }
+
+        assert_eq!(items(&app, &conn, "currency:EUR"), ["Tea", "Jam"]);
+        assert_eq!(items(&app, &conn, "currency:eur"), ["Tea", "Jam"]);
+        assert_eq!(items(&app, &conn, "currency:USD"), ["Egg"]);
+        assert_eq!(items(&app, &conn, "-currency:EUR"), ["Egg"]);
+    }
+
+    #[test]
+    fn stores() {
+        let conn = db::open(std::path::Path::new(":memory:")).unwrap();
+        let app = App::new();
+
+        for (description, store) in [("Tea", "Aldi"), ("Jam", ""), ("Egg", "Lidl")] {
+            let mut item = app.new_item.clone();
+            item.date = String::from("2026-10-01");
+            item.description = String::from(description);
+            item.store = String::from(store);
+            db::insert_item(&conn, &item).unwrap();
+        }
+
+        assert_eq!(items(&app, &conn, "store:aldi"), ["Tea"]);
+        assert_eq!(items(&app, &conn, "-store:Aldi"), ["Jam", "Egg"]);
+        assert_eq!(items(&app, &conn, "-store:Aldi -store:Lidl"), ["Jam"]);
+    }
+}
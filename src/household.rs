@@ -0,0 +1,398 @@
+use crate::app::{App, DbItem};
+use std::collections::BTreeMap;
+
+// Part of an item that a member of the household bears, when one member
+// paid for something others share.  Shares are an equal part of what is
+// left, a percentage of the price, or a fixed amount in the item's
+// currency.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Share {
+    pub member: String,
+
+    // Hundredths of a percent, or None.
+    pub percent: Option<i64>,
+
+    // Minor units of the item's currency, or None.
+    pub amount: Option<i64>,
+}
+
+// Money that one member paid another to settle up, in the home currency.
+#[derive(Clone, Debug)]
+pub struct Transfer {
+    pub id: i64,
+    pub date: String,
+    pub from: String,
+    pub to: String,
+    pub amount: i64,
+}
+
+// What a member paid for shared items, their part of those items, and
+// what they sent to others less what they received, in the home currency.
+// A positive balance is owed to the member, a negative one they owe.
+#[derive(Clone, Debug)]
+pub struct Balance {
+    pub member: String,
+    pub paid: i64,
+    pub share: i64,
+    pub transfers: i64,
+
+    // Shared items that could not be converted to the home currency.
+    pub unconverted: usize,
+}
+
+impl Balance {
+    pub fn balance(&self) -> i64 {
+        self.paid - self.share + self.transfers
+    }
+}
+
+// Shares as typed, e.g. "Ann; Bob" for equal parts, "Ann=60%; Bob=40%",
+// or "Ann=10; Bob; Cat", where Ann pays 10 and Bob and Cat halve the rest.
+// An empty line shares nothing.
+pub fn parse(app: &App, line: &str, price: i64, currency: &str) -> Result<Vec<Share>, String> {
+    if line.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut shares: Vec<Share> = Vec::new();
+
+    for part in line.split(';').map(str::trim) {
+        let (member, value) = match part.split_once('=') {
+            Some((member, value)) => (member.trim(), Some(value.trim())),
+            None => (part, None),
+        };
+        if member.is_empty() {
+            return Err(String::from("missing member"));
+        }
+        if shares.iter().any(|share| share.member == member) {
+            return Err(format!("{} shares twice", member));
+        }
+
+        let mut share = Share {
+            member: String::from(member),
+            percent: None,
+            amount: None,
+        };
+        match value {
+            Some(value) if value.ends_with('%') => {
+                share.percent = Some(
+                    crate::tax::parse_rate(value)
+                        .ok_or_else(|| format!("invalid percentage {}", value))?,
+                );
+            }
+            Some(value) => {
+                share.amount = Some(
+                    crate::expr::evaluate(&app.locale, value, app.exponent(currency))
+                        .ok_or_else(|| format!("invalid amount {}", value))?,
+                );
+            }
+            None => (),
+        }
+        shares.push(share);
+    }
+
+    match resolve(price, &shares) {
+        Ok(_) => Ok(shares),
+        Err(left) if left > 0 => Err(format!("{} left over", app.format_price(left, currency))),
+        Err(left) => Err(format!("{} too much", app.format_price(-left, currency))),
+    }
+}
+
+// Amount of each share of a price.  Percentages are rounded so that they
+// add up, and equal parts differ by at most one minor unit.  Fails with
+// what is left over, or negative if too much, when there are no equal
+// parts to take up the difference.
+pub fn resolve(price: i64, shares: &[Share]) -> Result<Vec<i64>, i64> {
+    let mut amounts = Vec::new();
+    let mut percent = 0;
+    let mut rest = price;
+
+    for share in shares {
+        let amount = match (share.percent, share.amount) {
+            (Some(p), _) => {
+                let amount = portion(price, percent + p) - portion(price, percent);
+                percent += p;
+                amount
+            }
+            (None, Some(amount)) => amount,
+            (None, None) => 0,
+        };
+        rest -= amount;
+        amounts.push(amount);
+    }
+
+    let equal = shares
+        .iter()
+        .filter(|share| share.percent.is_none() && share.amount.is_none())
+        .count() as i64;
+    if equal == 0 {
+        return if rest == 0 { Ok(amounts) } else { Err(rest) };
+    }
+    if rest != 0 && rest.signum() != price.signum() {
+        return Err(rest);
+    }
+
+    // Minor units that do not divide evenly go to the first members
+    let (each, mut extra) = (rest / equal, rest % equal);
+    for (share, amount) in shares.iter().zip(&mut amounts) {
+        if share.percent.is_none() && share.amount.is_none() {
+            *amount = each + extra.signum();
+            extra -= extra.signum();
+        }
+    }
+
+    Ok(amounts)
+}
+
+// Part of a price in hundredths of a percent, halves away from zero.
+fn portion(price: i64, percent: i64) -> i64 {
+    let num = i128::from(price) * i128::from(percent);
+    let part = (num.abs() * 2 + 10000) / 20000;
+    (num.signum() * part) as i64
+}
+
+// Shares in the form parse reads.
+pub fn to_line(app: &App, shares: &[Share], currency: &str) -> String {
+    shares
+        .iter()
+        .map(|share| match (share.percent, share.amount) {
+            (Some(percent), _) => format!("{}={}", share.member, crate::tax::format_rate(percent)),
+            (None, Some(amount)) => {
+                format!("{}={}", share.member, app.format_price(amount, currency))
+            }
+            (None, None) => share.member.clone(),
+        })
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+// Balance of every member from the shared items and transfers so far.
+// Items count once their payer has shared them.
+pub fn balances(
+    app: &App,
+    members: &[String],
+    items: impl Iterator<Item = DbItem>,
+    transfers: &[Transfer],
+) -> Vec<Balance> {
+    let mut map: BTreeMap<String, Balance> = BTreeMap::new();
+    for member in members {
+        get(&mut map, member);
+    }
+
+    let shared = items.filter(|item| !item.payer.is_empty() && !item.shares.is_empty());
+
+    for item in shared {
+        let amounts = match resolve(item.price, &item.shares) {
+            Ok(amounts) => amounts,
+            Err(_) => continue,
+        };
+
+        // Convert each share, so that the payer is owed exactly their sum
+        let converted: Option<Vec<i64>> = amounts
+            .iter()
+            .map(|&amount| app.home_amount(amount, &item.currency, &item.date))
+            .collect();
+
+        match converted {
+            Some(converted) => {
+                for (share, amount) in item.shares.iter().zip(&converted) {
+                    get(&mut map, &share.member).share += amount;
+                }
+                get(&mut map, &item.payer).paid += converted.iter().sum::<i64>();
+            }
+            None => get(&mut map, &item.payer).unconverted += 1,
+        }
+    }
+
+    for transfer in transfers {
+        get(&mut map, &transfer.from).transfers += transfer.amount;
+        get(&mut map, &transfer.to).transfers -= transfer.amount;
+    }
+
+    map.into_values().collect()
+}
+
+fn get<'m>(map: &'m mut BTreeMap<String, Balance>, member: &str) -> &'m mut Balance {
+    map.entry(String::from(member)).or_insert_with(|| Balance {
+        member: String::from(member),
+        paid: 0,
+        share: 0,
+        transfers: 0,
+        unconverted: 0,
+    })
+}
+
+// Transfers that bring every balance to zero: whoever owes most pays
+// whoever is owed most, until all are settled.
+pub fn settle(balances: &[Balance]) -> Vec<Transfer> {
+    let mut owing: Vec<(i64, &str)> = Vec::new();
+    let mut owed: Vec<(i64, &str)> = Vec::new();
+    for balance in balances {
+        match balance.balance() {
+            b if b < 0 => owing.push((-b, &balance.member)),
+            b if b > 0 => owed.push((b, &balance.member)),
+            _ => (),
+        }
+    }
+
+    let mut transfers = Vec::new();
+    loop {
+        owing.sort_by_key(|a| std::cmp::Reverse(a.0));
+        owed.sort_by_key(|a| std::cmp::Reverse(a.0));
+        let (from, to) = match (owing.first_mut(), owed.first_mut()) {
+            (Some(from), Some(to)) => (from, to),
+            _ => break,
+        };
+
+        let amount = from.0.min(to.0);
+        transfers.push(Transfer {
+            id: 0,
+            date: String::new(),
+            from: String::from(from.1),
+            to: String::from(to.1),
+            amount,
+        });
+        from.0 -= amount;
+        to.0 -= amount;
+
+        owing.retain(|&(amount, _)| amount > 0);
+        owed.retain(|&(amount, _)| amount > 0);
+    }
+
+    transfers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn share(member: &str, percent: Option<i64>, amount: Option<i64>) -> Share {
+        Share {
+            member: String::from(member),
+            percent,
+            amount,
+        }
+    }
+
+    #[test]
+    fn percentages_add_up() {
+        let shares = [
+            share("Ann", Some(3333), None),
+            share("Bob", Some(3333), None),
+            share("Cat", Some(3334), None),
+        ];
+        assert_eq!(resolve(1000, &shares), Ok(vec![333, 334, 333]));
+        assert_eq!(resolve(-1000, &shares), Ok(vec![-333, -334, -333]));
+
+        let shares = [
+            share("Ann", Some(5000), None),
+            share("Bob", Some(4000), None),
+        ];
+        assert_eq!(resolve(1000, &shares), Err(100));
+        let shares = [
+            share("Ann", Some(6000), None),
+            share("Bob", Some(5000), None),
+        ];
+        assert_eq!(resolve(1000, &shares), Err(-100));
+    }
+
+    #[test]
+    fn equal_parts() {
+        let shares = [
+            share("Ann", None, None),
+            share("Bob", None, None),
+            share("Cat", None, None),
+        ];
+        assert_eq!(resolve(1001, &shares), Ok(vec![334, 334, 333]));
+        assert_eq!(resolve(-1001, &shares), Ok(vec![-334, -334, -333]));
+        assert_eq!(resolve(0, &shares), Ok(vec![0, 0, 0]));
+
+        let shares = [
+            share("Ann", None, Some(1000)),
+            share("Bob", Some(2500), None),
+            share("Cat", None, None),
+            share("Dan", None, None),
+        ];
+        assert_eq!(resolve(10001, &shares), Ok(vec![1000, 2500, 3251, 3250]));
+
+        let shares = [share("Ann", None, Some(1200)), share("Bob", None, None)];
+        assert_eq!(resolve(1000, &shares), Err(-200));
+        let shares = [share("Ann", None, Some(-1200)), share("Bob", None, None)];
+        assert_eq!(resolve(-1000, &shares), Err(200));
+    }
+
+    #[test]
+    fn parse_and_print() {
+        let app = App::new();
+        let shares = parse(&app, "Ann=10; Bob=12.5%; Cat", 5000, "").unwrap();
+        assert_eq!(
+            shares,
+            vec![
+                share("Ann", None, Some(1000)),
+                share("Bob", Some(1250), None),
+                share("Cat", None, None),
+            ]
+        );
+        let line = to_line(&app, &shares, "");
+        assert_eq!(line, "Ann=10.00; Bob=12.5%; Cat");
+        assert_eq!(parse(&app, &line, 5000, ""), Ok(shares));
+
+        assert_eq!(parse(&app, "  ", 5000, ""), Ok(Vec::new()));
+        assert_eq!(
+            parse(&app, "Ann; Ann", 5000, ""),
+            Err(String::from("Ann shares twice"))
+        );
+        assert_eq!(
+            parse(&app, "=5; Bob", 5000, ""),
+            Err(String::from("missing member"))
+        );
+        assert!(parse(&app, "Ann=x%; Bob", 5000, "").is_err());
+        assert!(parse(&app, "Ann=1.2.3; Bob", 5000, "").is_err());
+        assert!(parse(&app, "Ann=50%", 5000, "")
+            .unwrap_err()
+            .ends_with("left over"));
+        assert!(parse(&app, "Ann=60; Bob", 5000, "")
+            .unwrap_err()
+            .ends_with("too much"));
+    }
+
+    #[test]
+    fn settle_up() {
+        let balance = |member: &str, paid, share| Balance {
+            member: String::from(member),
+            paid,
+            share,
+            transfers: 0,
+            unconverted: 0,
+        };
+        let mut balances = vec![
+            balance("Ann", 300, 100),
+            balance("Bob", 0, 50),
+            balance("Cat", 0, 150),
+        ];
+
+        let transfers = settle(&balances);
+        assert_eq!(transfers.len(), 2);
+        assert_eq!(
+            (transfers[0].from.as_str(), transfers[0].amount),
+            ("Cat", 150)
+        );
+        assert_eq!(
+            (transfers[1].from.as_str(), transfers[1].amount),
+            ("Bob", 50)
+        );
+
+        for transfer in &transfers {
+            for balance in &mut balances {
+                if balance.member == transfer.from {
+                    balance.transfers += transfer.amount;
+                }
+                if balance.member == transfer.to {
+                    balance.transfers -= transfer.amount;
+                }
+            }
+        }
+        assert!(balances.iter().all(|balance| balance.balance() == 0));
+        assert!(settle(&balances).is_empty());
+    }
+}
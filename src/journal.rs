@@ -0,0 +1,441 @@
+use rusqlite::{params, Connection, OptionalExtension, Result};
+
+// Every change to items is recorded in the journal table with an image of
+// the item before and after, as a JSON object of its columns, tags, split
+// allocations and shares.  A missing image means the item did not exist.
+// Changes made together share a step number, and are undone and redone as
+// a unit.  Tax rates of categories that move with their items are recorded
+// alongside, in the journal_category_tax table.
+pub struct Step {
+    number: i64,
+
+    // Items touched by the step, with their images before the change.
+    before: Vec<(i64, Option<String>)>,
+
+    // Categories whose tax rate the step may change, with the rate before.
+    taxes: Vec<(String, Option<String>)>,
+}
+
+impl Step {
+    // Start a new step.
+    pub fn begin(conn: &Connection) -> Result<Step> {
+        let number: i64 = conn.query_row(
+            "SELECT COALESCE(MAX(step), 0) + 1 FROM journal",
+            (),
+            |row| row.get(0),
+        )?;
+
+        Ok(Step {
+            number,
+            before: Vec::new(),
+            taxes: Vec::new(),
+        })
+    }
+
+    // Remember the item as it is now, before it is changed.
+    pub fn track(&mut self, conn: &Connection, id: i64) -> Result<()> {
+        let image = image(conn, id)?;
+        self.before.push((id, image));
+
+        Ok(())
+    }
+
+    // Remember that the item did not exist before the step.
+    pub fn track_new(&mut self, id: i64) {
+        self.before.push((id, None));
+    }
+
+    // Remember the tax rate of a category as it is now, before it is
+    // changed along with the items of the step.
+    pub fn track_category_tax(&mut self, conn: &Connection, category: &str) -> Result<()> {
+        let tax = category_tax(conn, category)?;
+        self.taxes.push((String::from(category), tax));
+
+        Ok(())
+    }
+
+    // Record what the step changed.  If it changed anything, this discards
+    // any undone steps, which can no longer be redone.
+    pub fn finish(self, conn: &Connection) -> Result<()> {
+        let mut taxes = Vec::new();
+        for (category, before) in self.taxes {
+            let after = category_tax(conn, &category)?;
+            if before != after {
+                taxes.push((category, before, after));
+            }
+        }
+
+        let mut items = Vec::new();
+        for (id, before) in self.before {
+            let after = image(conn, id)?;
+            if before != after {
+                items.push((id, before, after));
+            }
+        }
+
+        if taxes.is_empty() && items.is_empty() {
+            return Ok(());
+        }
+
+        conn.execute(
+            "DELETE FROM journal_category_tax
+                WHERE step IN (SELECT step FROM journal WHERE undone=1)",
+            (),
+        )?;
+        conn.execute("DELETE FROM journal WHERE undone=1", ())?;
+
+        for (category, before, after) in taxes {
+            conn.execute(
+                "INSERT INTO journal_category_tax(step, category, before, after)
+                    VALUES (?1, ?2, ?3, ?4)",
+                params![self.number, category, before, after],
+            )?;
+        }
+
+        for (id, before, after) in items {
+            conn.execute(
+                "INSERT INTO journal(step, item_id, before, after) VALUES (?1, ?2, ?3, ?4)",
+                params![self.number, id, before, after],
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+// Undo the latest step, returning the ids of the items it touched.
+pub fn undo(conn: &mut Connection) -> Result<Option<Vec<i64>>> {
+    let tx = conn.transaction()?;
+
+    let step: Option<i64> =
+        tx.query_row("SELECT MAX(step) FROM journal WHERE undone=0", (), |row| {
+            row.get(0)
+        })?;
+    let step = match step {
+        Some(step) => step,
+        None => return Ok(None),
+    };
+
+    let ids = replay(&tx, step, "before", "DESC")?;
+    tx.execute("UPDATE journal SET undone=1 WHERE step=?1", params![step])?;
+    tx.commit()?;
+
+    Ok(Some(ids))
+}
+
+// Redo the earliest undone step, returning the ids of the items it touched.
+pub fn redo(conn: &mut Connection) -> Result<Option<Vec<i64>>> {
+    let tx = conn.transaction()?;
+
+    let step: Option<i64> =
+        tx.query_row("SELECT MIN(step) FROM journal WHERE undone=1", (), |row| {
+            row.get(0)
+        })?;
+    let step = match step {
+        Some(step) => step,
+        None => return Ok(None),
+    };
+
+    let ids = replay(&tx, step, "after", "ASC")?;
+    tx.execute("UPDATE journal SET undone=0 WHERE step=?1", params![step])?;
+    tx.commit()?;
+
+    Ok(Some(ids))
+}
+
+// Items that the next undo, or redo, would touch.
+pub fn next_items(conn: &Connection, redo: bool) -> Result<Vec<i64>> {
+    let step = if redo {
+        "SELECT MIN(step) FROM journal WHERE undone=1"
+    } else {
+        "SELECT MAX(step) FROM journal WHERE undone=0"
+    };
+    let mut stmt = conn.prepare(&format!(
+        "SELECT DISTINCT item_id FROM journal WHERE step=({})",
+        step
+    ))?;
+    let ids = stmt.query_map((), |row| row.get(0))?.collect();
+
+    ids
+}
+
+// Restore the before or after images of a step, in the given order.
+fn replay(conn: &Connection, step: i64, image: &str, order: &str) -> Result<Vec<i64>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT item_id, {} FROM journal WHERE step=?1 ORDER BY id {}",
+        image, order
+    ))?;
+    let rows: Vec<(i64, Option<String>)> = stmt
+        .query_map(params![step], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<_>>()?;
+
+    for (id, image) in &rows {
+        restore(conn, *id, image.as_deref())?;
+    }
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT category, {} FROM journal_category_tax WHERE step=?1 ORDER BY id {}",
+        image, order
+    ))?;
+    let taxes: Vec<(String, Option<String>)> = stmt
+        .query_map(params![step], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<_>>()?;
+
+    // Rates deleted since are not brought back
+    for (category, tax) in &taxes {
+        conn.execute(
+            "DELETE FROM category_tax WHERE category=?1",
+            params![category],
+        )?;
+        conn.execute(
+            "INSERT INTO category_tax(category, tax_rate)
+                SELECT ?1, name FROM tax_rates WHERE name=?2",
+            params![category, tax],
+        )?;
+    }
+
+    Ok(rows.into_iter().map(|(id, _)| id).collect())
+}
+
+// Drop every step that touched an item about to be purged, since a step
+// can only be undone or redone as a whole, and the item's id from the
+// images of items that were refunds of it.
+pub fn forget(conn: &Connection, id: i64) -> Result<()> {
+    conn.execute(
+        "DELETE FROM journal_category_tax
+            WHERE step IN (SELECT step FROM journal WHERE item_id=?1)",
+        params![id],
+    )?;
+    conn.execute(
+        "DELETE FROM journal WHERE step IN (SELECT step FROM journal WHERE item_id=?1)",
+        params![id],
+    )?;
+
+    for image in ["before", "after"] {
+        conn.execute(
+            &format!(
+                "UPDATE journal SET {0}=json_set({0}, '$.original_id', NULL)
+                    WHERE json_extract({0}, '$.original_id')=?1",
+                image
+            ),
+            params![id],
+        )?;
+    }
+
+    Ok(())
+}
+
+fn category_tax(conn: &Connection, category: &str) -> Result<Option<String>> {
+    conn.query_row(
+        "SELECT tax_rate FROM category_tax WHERE category=?1",
+        params![category],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+// Column names of the items table, which grows with each migration.
+fn columns(conn: &Connection) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT name FROM pragma_table_info('items')")?;
+    let columns = stmt.query_map((), |row| row.get(0))?.collect();
+
+    columns
+}
+
+fn image(conn: &Connection, id: i64) -> Result<Option<String>> {
+    let fields: Vec<String> = columns(conn)?
+        .iter()
+        .map(|column| format!("'{0}', \"{0}\"", column))
+        .collect();
+
+    let sql = format!(
+        "SELECT json_object({}, 'tags', json((
+             SELECT json_group_array(name) FROM (
+                 SELECT tags.name FROM item_tags
+                     JOIN tags ON tags.id = item_tags.tag_id
+                     WHERE item_tags.item_id = items.id
+                     ORDER BY tags.name))),
+             'splits', json((
+             SELECT json_group_array(json_object('category', category, 'price', price)) FROM (
+                 SELECT category, price FROM item_splits
+                     WHERE item_splits.item_id = items.id
+                     ORDER BY position))),
+             'shares', json((
+             SELECT json_group_array(json_object(
+                     'member_id', member_id, 'percent', percent, 'amount', amount)) FROM (
+                 SELECT member_id, percent, amount FROM item_shares
+                     WHERE item_shares.item_id = items.id
+                     ORDER BY position))))
+             FROM items WHERE id=?1",
+        fields.join(", ")
+    );
+
+    conn.query_row(&sql, params![id], |row| row.get(0))
+        .optional()
+}
+
+fn restore(conn: &Connection, id: i64, image: Option<&str>) -> Result<()> {
+    conn.execute("DELETE FROM item_tags WHERE item_id=?1", params![id])?;
+    conn.execute("DELETE FROM item_splits WHERE item_id=?1", params![id])?;
+    conn.execute("DELETE FROM item_shares WHERE item_id=?1", params![id])?;
+
+    let image = match image {
+        Some(image) => image,
+        None => {
+            conn.execute("DELETE FROM items WHERE id=?1", params![id])?;
+            return Ok(());
+        }
+    };
+
+    let columns = columns(conn)?;
+    let names: Vec<String> = columns.iter().map(|c| format!("\"{}\"", c)).collect();
+    let values: Vec<String> = columns
+        .iter()
+        .map(|c| format!("json_extract(?1, '$.{}')", c))
+        .collect();
+
+    // Delete and insert rather than INSERT OR REPLACE, which would not fire
+    // the delete trigger that keeps the full-text index in sync.
+    conn.execute("DELETE FROM items WHERE id=?1", params![id])?;
+    conn.execute(
+        &format!(
+            "INSERT INTO items({}) SELECT {}",
+            names.join(", "),
+            values.join(", ")
+        ),
+        params![image],
+    )?;
+
+    conn.execute(
+        "INSERT OR IGNORE INTO tags(name) SELECT value FROM json_each(?1, '$.tags')",
+        params![image],
+    )?;
+    conn.execute(
+        "INSERT INTO item_tags(item_id, tag_id)
+            SELECT ?2, tags.id FROM json_each(?1, '$.tags') JOIN tags ON tags.name = value",
+        params![image, id],
+    )?;
+    conn.execute(
+        "INSERT INTO item_splits(item_id, position, category, price)
+            SELECT ?2, key, json_extract(value, '$.category'), json_extract(value, '$.price')
+                FROM json_each(?1, '$.splits')",
+        params![image, id],
+    )?;
+    conn.execute(
+        "INSERT INTO item_shares(item_id, position, member_id, percent, amount)
+            SELECT ?2, key, json_extract(value, '$.member_id'),
+                   json_extract(value, '$.percent'), json_extract(value, '$.amount')
+                FROM json_each(?1, '$.shares')",
+        params![image, id],
+    )?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::App;
+    use crate::db;
+
+    fn insert(conn: &Connection, category: &str, description: &str) -> i64 {
+        let mut item = App::new().new_item;
+        item.date = String::from("2026-10-01");
+        item.category = String::from(category);
+        item.description = String::from(description);
+        item.price = 100;
+        db::insert_item(conn, &item).unwrap()
+    }
+
+    fn count(conn: &Connection, sql: &str) -> i64 {
+        conn.query_row(sql, (), |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn undo_and_redo() {
+        let mut conn = db::open(std::path::Path::new(":memory:")).unwrap();
+        let tea = insert(&conn, "Food", "Tea");
+        db::set_item_tags(&mut conn, tea, &[String::from("organic")]).unwrap();
+        let inserted = image(&conn, tea).unwrap();
+
+        let mut changed = db::select_item(&conn, tea).unwrap().unwrap();
+        changed.price = 250;
+        changed.category = String::from("Drinks");
+        db::update_item(&conn, tea, &changed).unwrap();
+        let updated = image(&conn, tea).unwrap();
+        assert_ne!(inserted, updated);
+
+        db::delete_item(&conn, tea).unwrap();
+        let deleted = image(&conn, tea).unwrap();
+        assert!(deleted.as_ref().unwrap().contains("deleted_at\":\"2"));
+
+        assert_eq!(undo(&mut conn).unwrap(), Some(vec![tea]));
+        assert_eq!(image(&conn, tea).unwrap(), updated);
+        assert_eq!(undo(&mut conn).unwrap(), Some(vec![tea]));
+        assert_eq!(image(&conn, tea).unwrap(), inserted);
+        assert_eq!(undo(&mut conn).unwrap(), Some(vec![tea]));
+        assert_eq!(undo(&mut conn).unwrap(), Some(vec![tea]));
+        assert_eq!(image(&conn, tea).unwrap(), None);
+        assert_eq!(undo(&mut conn).unwrap(), None);
+
+        assert_eq!(redo(&mut conn).unwrap(), Some(vec![tea]));
+        assert_eq!(redo(&mut conn).unwrap(), Some(vec![tea]));
+        assert_eq!(image(&conn, tea).unwrap(), inserted);
+        assert_eq!(redo(&mut conn).unwrap(), Some(vec![tea]));
+        assert_eq!(image(&conn, tea).unwrap(), updated);
+        assert_eq!(redo(&mut conn).unwrap(), Some(vec![tea]));
+        assert_eq!(image(&conn, tea).unwrap(), deleted);
+        assert_eq!(redo(&mut conn).unwrap(), None);
+    }
+
+    #[test]
+    fn unchanged_step_keeps_redo() {
+        let mut conn = db::open(std::path::Path::new(":memory:")).unwrap();
+        let tea = insert(&conn, "Food", "Tea");
+        db::set_item_tags(&mut conn, tea, &[String::from("organic")]).unwrap();
+        undo(&mut conn).unwrap();
+
+        // Saving the same tags again changes nothing
+        db::set_item_tags(&mut conn, tea, &[]).unwrap();
+        assert_eq!(next_items(&conn, true).unwrap(), [tea]);
+
+        // A real change does discard it
+        db::set_item_tags(&mut conn, tea, &[String::from("sale")]).unwrap();
+        assert_eq!(next_items(&conn, true).unwrap(), Vec::<i64>::new());
+    }
+
+    #[test]
+    fn purge_whole_steps() {
+        let mut conn = db::open(std::path::Path::new(":memory:")).unwrap();
+        let tea = insert(&conn, "Food", "Tea");
+        let jam = insert(&conn, "Food", "Jam");
+        db::merge_categories(&mut conn, &[String::from("Food")], "Groceries").unwrap();
+        db::delete_item(&conn, jam).unwrap();
+        conn.execute(
+            "UPDATE items SET original_id=?1 WHERE id=?2",
+            params![jam, tea],
+        )
+        .unwrap();
+        conn.execute(
+            "UPDATE journal SET after=json_set(after, '$.original_id', ?1)",
+            params![jam],
+        )
+        .unwrap();
+
+        db::purge_items(&mut conn, Some(jam), None, &mut |_, _| true).unwrap();
+
+        // Only the insert of the tea is left to undo, not half the merge
+        assert_eq!(count(&conn, "SELECT COUNT(DISTINCT step) FROM journal"), 1);
+        assert_eq!(next_items(&conn, false).unwrap(), [tea]);
+        assert_eq!(
+            count(
+                &conn,
+                "SELECT COUNT(*) FROM journal WHERE json_extract(after, '$.original_id') IS NOT NULL"
+            ),
+            0
+        );
+        assert_eq!(undo(&mut conn).unwrap(), Some(vec![tea]));
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM items"), 0);
+        assert_eq!(undo(&mut conn).unwrap(), None);
+    }
+}
@@ -0,0 +1,368 @@
+use crate::db;
+use rusqlite::{Connection, Result};
+
+// How amounts are written on screen and read back, e.g. "1 234,50 €" or
+// "$1,234.50".  The default is "1234.50", with codes for currencies.
+// Amounts are in minor units, see currency::MinorUnits.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Locale {
+    // Between whole and minor units, "." or ",".
+    pub decimal: char,
+
+    // Between groups of thousands, if any.
+    pub grouping: Option<char>,
+
+    // Symbol of the home currency, shown instead of its code, and whether
+    // it goes after the amount.
+    pub symbol: String,
+    pub symbol_after: bool,
+}
+
+// Symbols of common currencies, accepted when pasted.  The dollar sign is
+// taken to mean US dollars unless it is the symbol of the home currency.
+const SYMBOLS: &[(&str, &str)] = &[
+    ("US$", "USD"),
+    ("$", "USD"),
+    ("€", "EUR"),
+    ("£", "GBP"),
+    ("¥", "JPY"),
+    ("₹", "INR"),
+    ("₩", "KRW"),
+    ("₽", "RUB"),
+    ("₺", "TRY"),
+    ("₪", "ILS"),
+    ("zł", "PLN"),
+];
+
+impl Default for Locale {
+    fn default() -> Locale {
+        Locale {
+            decimal: '.',
+            grouping: None,
+            symbol: String::new(),
+            symbol_after: false,
+        }
+    }
+}
+
+impl Locale {
+    pub fn load(conn: &Connection) -> Result<Locale> {
+        let mut locale = Locale::default();
+
+        if let Some(decimal) = db::get_setting(conn, "decimal_separator")? {
+            locale.decimal = decimal.chars().next().unwrap_or(locale.decimal);
+        }
+        if let Some(grouping) = db::get_setting(conn, "grouping_separator")? {
+            locale.grouping = grouping.chars().next().filter(|&c| c != locale.decimal);
+        }
+        locale.symbol = db::get_setting(conn, "currency_symbol")?.unwrap_or_default();
+        locale.symbol_after = db::get_setting(conn, "symbol_position")?.as_deref() == Some("after");
+
+        Ok(locale)
+    }
+
+    // Amount without a currency, e.g. "-1 234,50", with `exponent` digits
+    // after the decimal separator.
+    pub fn format_price(&self, price: i64, exponent: u32) -> String {
+        let sign = if price < 0 { "-" } else { "" };
+        let price = price.unsigned_abs();
+        let unit = 10u64.pow(exponent);
+        let digits = (price / unit).to_string();
+
+        let mut whole = String::new();
+        for (i, c) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i).is_multiple_of(3) {
+                whole.extend(self.grouping);
+            }
+            whole.push(c);
+        }
+
+        if exponent == 0 {
+            return format!("{}{}", sign, whole);
+        }
+        let width = exponent as usize;
+        format!("{}{}{}{:0width$}", sign, whole, self.decimal, price % unit)
+    }
+
+    // Amount in the home currency with its symbol, e.g. "-$1.50" or
+    // "-1,50 €".
+    pub fn format_symbol(&self, price: i64, exponent: u32) -> String {
+        if self.symbol_after {
+            format!("{} {}", self.format_price(price, exponent), self.symbol)
+        } else if price < 0 {
+            format!("-{}{}", self.symbol, self.format_price(-price, exponent))
+        } else {
+            format!("{}{}", self.symbol, self.format_price(price, exponent))
+        }
+    }
+
+    // Amount with an optional sign and grouping, e.g. "-1 234,50", in minor
+    // units with `exponent` digits.  Where neither "." nor "," groups
+    // thousands, either may separate the fraction.
+    pub fn parse_price(&self, text: &str, exponent: u32) -> Option<i64> {
+        if let Some(text) = text.strip_prefix('-') {
+            return self
+                .parse_unsigned_price(text, exponent)
+                .map(|price| -price);
+        }
+        self.parse_unsigned_price(text.strip_prefix('+').unwrap_or(text), exponent)
+    }
+
+    // Unsigned number with grouping and up to six decimals, e.g. "1,5" or
+    // "0.375", as read by parse_price.
+    pub fn parse_decimal(&self, text: &str) -> Option<f64> {
+        let (_, fraction) = self.split_decimal(text);
+        let digits = u32::try_from(fraction.len()).ok().filter(|&n| n <= 6)?;
+        let number = self.parse_unsigned_price(text, digits)?;
+        Some(number as f64 / 10f64.powi(digits as i32))
+    }
+
+    fn parse_unsigned_price(&self, text: &str, exponent: u32) -> Option<i64> {
+        let (whole, fraction) = self.split_decimal(text);
+
+        // Fewer digits than the currency has are padded, e.g. "1.5" is 150
+        // cents, and more are rejected rather than rounded
+        let digits = u32::try_from(fraction.len())
+            .ok()
+            .filter(|&n| n <= exponent)?;
+        if !fraction.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        let fraction = match fraction {
+            "" => 0,
+            _ => fraction.parse::<i64>().ok()? * 10i64.pow(exponent - digits),
+        };
+
+        // Groups after the first must have three digits, so that "1,5" is
+        // not read as 15 where "," groups thousands
+        let groups: Vec<&str> = whole.split(|c| self.is_grouping(c)).collect();
+        let well_formed = groups.iter().enumerate().all(|(i, group)| {
+            let len_ok = if i == 0 {
+                (1..=3).contains(&group.len()) || groups.len() == 1
+            } else {
+                group.len() == 3
+            };
+            len_ok && !group.is_empty() && group.chars().all(|c| c.is_ascii_digit())
+        });
+        if !well_formed {
+            return None;
+        }
+
+        groups
+            .concat()
+            .parse::<i64>()
+            .ok()?
+            .checked_mul(10i64.pow(exponent))?
+            .checked_add(fraction)
+    }
+
+    // Whole and fractional part of a number, split at the last decimal
+    // separator.
+    fn split_decimal<'t>(&self, text: &'t str) -> (&'t str, &'t str) {
+        let is_decimal =
+            |c: char| c == self.decimal || (matches!(c, '.' | ',') && Some(c) != self.grouping);
+
+        match text.char_indices().rfind(|&(_, c)| is_decimal(c)) {
+            Some((i, c)) => (&text[..i], &text[i + c.len_utf8()..]),
+            None => (text, ""),
+        }
+    }
+
+    // Spaces that groups thousands may also be pasted as no-break spaces.
+    fn is_grouping(&self, c: char) -> bool {
+        match self.grouping {
+            Some(g) if g.is_whitespace() => c.is_whitespace(),
+            Some(g) => c == g,
+            None => false,
+        }
+    }
+
+    // Currency symbol or code at the start of text, with the rest of the
+    // text.  The home currency is returned as its code, or empty for the
+    // configured symbol.  Codes count only if `known` accepts them, so that
+    // other three-letter words are not taken for currencies.
+    pub fn currency_prefix<'t>(
+        &self,
+        text: &'t str,
+        known: impl Fn(&str) -> bool,
+    ) -> Option<(String, &'t str)> {
+        let text = text.trim();
+
+        for (symbol, code) in self.symbols() {
+            if let Some(rest) = text.strip_prefix(symbol) {
+                return Some((String::from(code), rest.trim()));
+            }
+        }
+
+        // Codes may touch the amount but not other letters, e.g. "USD 12"
+        // or "USD12"
+        let code = text.get(..3).and_then(crate::currency::parse_code)?;
+        if !known(&code) || text[3..].starts_with(char::is_alphabetic) {
+            return None;
+        }
+        Some((code, text[3..].trim()))
+    }
+
+    // Currency symbol or code at the end of text, as for currency_prefix.
+    pub fn currency_suffix<'t>(
+        &self,
+        text: &'t str,
+        known: impl Fn(&str) -> bool,
+    ) -> Option<(String, &'t str)> {
+        let text = text.trim();
+
+        for (symbol, code) in self.symbols() {
+            if let Some(rest) = text.strip_suffix(symbol) {
+                return Some((String::from(code), rest.trim()));
+            }
+        }
+
+        let start = text.len().checked_sub(3)?;
+        let code = text.get(start..).and_then(crate::currency::parse_code)?;
+        if !known(&code) || text[..start].ends_with(char::is_alphabetic) {
+            return None;
+        }
+        Some((code, text[..start].trim()))
+    }
+
+    fn symbols(&self) -> impl Iterator<Item = (&str, &str)> {
+        std::iter::once((self.symbol.as_str(), ""))
+            .chain(SYMBOLS.iter().copied())
+            .filter(|(symbol, _)| !symbol.is_empty())
+    }
+
+    // Whether c may be part of a number, i.e. a digit or separator.
+    pub fn is_number_char(&self, c: char) -> bool {
+        c.is_ascii_digit() || matches!(c, '.' | ',') || c == self.decimal || self.is_grouping(c)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn german() -> Locale {
+        Locale {
+            decimal: ',',
+            grouping: Some('.'),
+            symbol: String::from("€"),
+            symbol_after: true,
+        }
+    }
+
+    #[test]
+    fn format() {
+        let plain = Locale::default();
+        assert_eq!(plain.format_price(123450, 2), "1234.50");
+        assert_eq!(plain.format_price(-5, 2), "-0.05");
+        assert_eq!(plain.format_price(1234, 0), "1234");
+        assert_eq!(plain.format_price(1234, 3), "1.234");
+
+        let german = german();
+        assert_eq!(german.format_price(123456789, 2), "1.234.567,89");
+        assert_eq!(german.format_price(-100000, 2), "-1.000,00");
+        assert_eq!(german.format_symbol(-150, 2), "-1,50 €");
+
+        let us = Locale {
+            grouping: Some(','),
+            symbol: String::from("$"),
+            ..Locale::default()
+        };
+        assert_eq!(us.format_symbol(-150, 2), "-$1.50");
+        assert_eq!(us.format_symbol(123450, 2), "$1,234.50");
+    }
+
+    #[test]
+    fn parse() {
+        let plain = Locale::default();
+        assert_eq!(plain.parse_price("12", 2), Some(1200));
+        assert_eq!(plain.parse_price("1.5", 2), Some(150));
+        assert_eq!(plain.parse_price("1,5", 2), Some(150));
+        assert_eq!(plain.parse_price("+1.50", 2), Some(150));
+        assert_eq!(plain.parse_price("-0.05", 2), Some(-5));
+        assert_eq!(plain.parse_price("1.234", 3), Some(1234));
+        assert_eq!(plain.parse_price("1.234", 2), None);
+        assert_eq!(plain.parse_price("1.5", 0), None);
+        assert_eq!(plain.parse_price("", 2), None);
+        assert_eq!(plain.parse_price("-", 2), None);
+        assert_eq!(plain.parse_price("--1", 2), None);
+        assert_eq!(plain.parse_price("1.-5", 2), None);
+        assert_eq!(plain.parse_price("99999999999999999999", 2), None);
+
+        let german = german();
+        assert_eq!(german.parse_price("1.234,50", 2), Some(123450));
+        assert_eq!(german.parse_price("1234,5", 2), Some(123450));
+        assert_eq!(german.parse_price("1,5", 2), Some(150));
+        assert_eq!(german.parse_price("1.5", 2), None);
+        assert_eq!(german.parse_price("12.34.567", 2), None);
+        assert_eq!(german.parse_decimal("1.000,25"), Some(1000.25));
+        assert_eq!(german.parse_decimal("0,375"), Some(0.375));
+        assert_eq!(german.parse_decimal("1.5"), None);
+        assert_eq!(german.parse_decimal("0,1234567"), None);
+    }
+
+    #[test]
+    fn parse_spaces_and_wide_separators() {
+        let french = Locale {
+            decimal: ',',
+            grouping: Some(' '),
+            ..Locale::default()
+        };
+        assert_eq!(french.parse_price("1 234,50", 2), Some(123450));
+        assert_eq!(french.parse_price("1\u{a0}234,50", 2), Some(123450));
+        assert_eq!(french.parse_price("1 23,50", 2), None);
+
+        let arabic = Locale {
+            decimal: '٫',
+            grouping: Some('٬'),
+            ..Locale::default()
+        };
+        assert_eq!(arabic.parse_price("1٬234٫5", 2), Some(123450));
+        assert_eq!(arabic.parse_price("٫", 2), None);
+        assert_eq!(arabic.parse_price("1٫€", 2), None);
+        assert_eq!(arabic.format_price(123450, 2), "1٬234٫50");
+    }
+
+    #[test]
+    fn currencies() {
+        let german = german();
+        let code = |text| {
+            german
+                .currency_prefix(text, |code| code != "XXX")
+                .map(|(code, rest)| (code, String::from(rest)))
+        };
+        assert_eq!(
+            code("USD 12"),
+            Some((String::from("USD"), String::from("12")))
+        );
+        assert_eq!(
+            code("usd12"),
+            Some((String::from("USD"), String::from("12")))
+        );
+        assert_eq!(code("€1,50"), Some((String::new(), String::from("1,50"))));
+        assert_eq!(code("$1"), Some((String::from("USD"), String::from("1"))));
+        assert_eq!(code("USDX 1"), None);
+        assert_eq!(code("XXX 1"), None);
+        assert_eq!(code("12"), None);
+        assert_eq!(code("€"), Some((String::new(), String::new())));
+
+        let code = |text| {
+            german
+                .currency_suffix(text, |code| code != "XXX")
+                .map(|(code, rest)| (code, String::from(rest)))
+        };
+        assert_eq!(
+            code("12,50 USD"),
+            Some((String::from("USD"), String::from("12,50")))
+        );
+        assert_eq!(code("1,50 €"), Some((String::new(), String::from("1,50"))));
+        assert_eq!(
+            code("12 zł"),
+            Some((String::from("PLN"), String::from("12")))
+        );
+        assert_eq!(code("1 XUSD"), None);
+        assert_eq!(code("1 XXX"), None);
+        assert_eq!(code("€"), Some((String::new(), String::new())));
+        assert_eq!(code("é"), None);
+    }
+}
@@ -2,18 +2,47 @@
 extern crate rusqlite;
 
 mod app;
+mod config;
+mod currency;
+mod db;
+mod expr;
+mod filter;
+mod household;
+mod journal;
+mod locale;
+mod model;
+mod quantity;
+mod split;
+mod stats;
+mod tax;
 mod ui;
 mod util;
+mod window;
+mod worker;
 
-use crate::app::{App, AppState, DbItem};
+use crate::app::{App, AppState, DbReceipt, ItemKind, SortOrder, TagFilter};
+use crate::currency::MinorUnits;
+use crate::household::{Balance, Transfer};
+use crate::stats::{CategoryNode, Cheapest, StoreStats, TaxRow};
+use crate::worker::{Job, Outcome, Scan, Stats};
 use crossterm::event::{KeyCode, KeyEvent};
 use ratatui::prelude::{CrosstermBackend, Terminal};
-use rusqlite::{params, Connection, Result};
+use rusqlite::{Connection, Result};
 use tui_textarea::CursorMove;
 
-const DATABASE_FILE: &str = "shopping.db";
-
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Open database before touching the terminal, so errors are readable
+    let config = config::Config::from_args().unwrap_or_else(|err| fatal(err));
+    if !config.database.exists() && !config::confirm_create(&config.database)? {
+        return Ok(());
+    }
+    let mut conn = db::open(&config.database).unwrap_or_else(|err| fatal(err));
+
+    if config.is_batch() {
+        return run_batch(&config, &mut conn).map_err(|err| fatal(err));
+    }
+    let worker = worker::Worker::start(&config.database).unwrap_or_else(|err| fatal(err));
+
     // Setup terminal
     crossterm::terminal::enable_raw_mode()?;
     crossterm::execute!(std::io::stderr(), crossterm::terminal::EnterAlternateScreen)?;
@@ -21,19 +50,68 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Create app and run it
     let mut app = App::new();
-    let mut conn = Connection::open(DATABASE_FILE)?;
-    create_database(&conn)?;
 
-    app.items = select_items(&conn)?;
-    app.distinct_categories = select_categories(&conn)?;
-    app.distinct_descriptions = select_descriptions(&conn)?;
-    app.table_state.select(navigate_home(&app.items));
+    if let Some(sort) = db::get_setting(&conn, "sort")?.and_then(|s| SortOrder::parse(&s)) {
+        app.sort = sort;
+    }
+    model::load_settings(&mut app, &conn)?;
+    app.date_format = db::get_setting(&conn, "date_format")?.unwrap_or_default();
+    app.locale = locale::Locale::load(&conn)?;
+    app.saved_filters = db::select_filters(&conn)?;
+    model::reload(&mut app, &conn)?;
 
     loop {
+        if let Some(busy) = &mut app.busy {
+            if let Some((message, is_error, outcome)) = worker.poll(busy) {
+                app.busy = None;
+                if !message.is_empty() {
+                    app.message = message;
+                    app.message_is_error = is_error;
+                }
+                match outcome {
+                    Outcome::Reloaded(snapshot) => {
+                        model::show(&mut app, *snapshot);
+
+                        // The database changed under the statistics shown
+                        if app.state == AppState::Stores {
+                            app.job = Some(Job::Scan(Scan::Stores));
+                        }
+                    }
+                    Outcome::Scanned(stats) => show_scan(&mut app, stats),
+                    Outcome::Done => (),
+                }
+                if app.state == AppState::Trash {
+                    show_trash(&mut app, &conn)?;
+                }
+            }
+        }
+
+        // The worker's changes are noticed here once it has finished
+        if app.busy.is_none() {
+            model::check_version(&mut app, &conn)?;
+            if let Some(job) = app.job.take() {
+                app.busy = Some(worker.submit(job));
+            }
+        }
         terminal.draw(|f| ui::render_tui(f, &mut app))?;
 
-        if crossterm::event::poll(std::time::Duration::from_millis(250))? {
+        // Redraw more often while busy, to animate the indicator
+        let timeout = if app.busy.is_some() { 100 } else { 250 };
+        if crossterm::event::poll(std::time::Duration::from_millis(timeout))? {
             if let crossterm::event::Event::Key(key) = crossterm::event::read()? {
+                app.message.clear();
+
+                if app.busy.is_some() {
+                    // Leave the database to the worker, apart from stopping it
+                    if key.code == KeyCode::F(10) {
+                        worker.cancel();
+                        break;
+                    } else if key.code == KeyCode::Esc {
+                        worker.cancel();
+                    }
+                    continue;
+                }
+
                 if key.code == KeyCode::F(10) {
                     break;
                 } else if key.code == KeyCode::Esc {
@@ -43,11 +121,41 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
                 match app.state {
                     AppState::Browse => main_browse(&mut app, key, &mut conn)?,
+                    AppState::Stores => main_stores(&mut app, key),
+                    AppState::Categories => main_categories(&mut app, key),
+                    AppState::TaxRate => main_tax_rate(&mut app, key, &mut conn)?,
+                    AppState::CategoryTax => main_category_tax(&mut app, key, &mut conn)?,
+                    AppState::TaxReport => main_tax_report(&mut app, key),
+                    AppState::SplitItem => main_split_item(&mut app, key, &mut conn)?,
+                    AppState::Payer => main_payer(&mut app, key, &mut conn)?,
+                    AppState::Shares => main_shares(&mut app, key, &mut conn)?,
+                    AppState::Balances => main_balances(&mut app, key, &mut conn)?,
+                    AppState::SettleUp => main_settle_up(&mut app, key, &mut conn)?,
+                    AppState::Trash => main_trash(&mut app, key, &mut conn)?,
+                    AppState::EmptyTrash => main_empty_trash(&mut app, key),
+                    AppState::PurgeItem => main_purge_item(&mut app, key, &mut conn)?,
+                    AppState::ImportRates => main_import_rates(&mut app, key),
+                    AppState::RenameCategory => main_rename_category(&mut app, key),
+                    AppState::MergeCategories => main_merge_categories(&mut app, key),
+                    AppState::EditTags => main_edit_tags(&mut app, key, &mut conn)?,
+                    AppState::FilterTags => main_filter_tags(&mut app, key),
+                    AppState::Search => main_search(&mut app, key, &conn)?,
+                    AppState::Filter => main_filter(&mut app, key),
+                    AppState::SaveFilter => main_save_filter(&mut app, key, &conn)?,
+                    AppState::ReceiptDate => main_receipt_date(&mut app, key),
+                    AppState::ReceiptStore => main_receipt_store(&mut app, key),
+                    AppState::ReceiptTotal => main_receipt_total(&mut app, key),
                     AppState::InsertDate => main_insert_date(&mut app, key),
                     AppState::InsertDescription => main_insert_description(&mut app, key, &conn)?,
-                    AppState::InsertCategory => main_insert_category(&mut app, key),
+                    AppState::InsertCategory => main_insert_category(&mut app, key, &conn),
+                    AppState::InsertStore => main_insert_store(&mut app, key),
+                    AppState::InsertQuantity => main_insert_quantity(&mut app, key),
                     AppState::InsertPrice => main_insert_price(&mut app, key, &conn)?,
                 };
+
+                if let Some(job) = app.job.take() {
+                    app.busy = Some(worker.submit(job));
+                }
             }
         }
     }
@@ -59,56 +167,379 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+fn run_batch(
+    config: &config::Config,
+    conn: &mut Connection,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for (key, value) in &config.settings {
+        if key == "minor_units" {
+            set_minor_units(conn, value)?;
+            continue;
+        }
+        if key == "home_currency" {
+            set_home_currency(conn, value)?;
+            continue;
+        }
+        db::set_setting(conn, key, value)?;
+        println!("{} = {}", key, value);
+    }
+
+    if let Some(path) = &config.import_rates {
+        let rates = currency::read_csv(path)?;
+        db::insert_rates(conn, &rates, &mut |_, _| true)?;
+        println!("Imported {} exchange rates", rates.len());
+    }
+
+    for (name, rate) in &config.tax_rates {
+        db::set_tax_rate(conn, name, *rate)?;
+        match rate {
+            Some(rate) => println!("{} = {}", name, tax::format_rate(*rate)),
+            None => println!("Deleted tax rate {}", name),
+        }
+    }
+
+    Ok(())
+}
+
+// Add to the minor units setting, and rescale the prices already stored
+// in the currencies it changes.
+fn set_minor_units(conn: &mut Connection, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let home = db::get_setting(conn, "home_currency")?.unwrap_or_default();
+    let old = db::get_setting(conn, "minor_units")?
+        .and_then(|setting| MinorUnits::parse(&setting))
+        .unwrap_or_default();
+    let added = MinorUnits::parse(value).ok_or("invalid minor_units")?;
+    let mut new = old.clone();
+    new.merge(&added);
+
+    for code in added.codes() {
+        let shift = new.exponent(code) as i32 - old.exponent(code) as i32;
+        if shift == 0 {
+            continue;
+        }
+        let mut n = db::rescale_prices(conn, code, shift)?;
+        if *code == home {
+            n += db::rescale_prices(conn, "", shift)?;
+        }
+        println!("Rescaled {} prices in {}", n, code);
+    }
+
+    db::set_setting(conn, "minor_units", &new.to_setting())?;
+    println!("minor_units = {}", new.to_setting());
+
+    Ok(())
+}
+
+// Change the home currency without changing what the prices already
+// stored mean, see db::set_home_currency.
+fn set_home_currency(conn: &mut Connection, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let old = db::get_setting(conn, "home_currency")?.unwrap_or_default();
+    if old == value {
+        println!("home_currency = {}", value);
+        return Ok(());
+    }
+    let units = db::get_setting(conn, "minor_units")?
+        .and_then(|setting| MinorUnits::parse(&setting))
+        .unwrap_or_default();
+    let old_exponent = if old.is_empty() {
+        2
+    } else {
+        units.exponent(&old)
+    };
+    let shift = units.exponent(value) as i32 - old_exponent as i32;
+
+    let mut transfers = Vec::new();
+    if !old.is_empty() {
+        let rates = currency::Rates::new(db::select_rates(conn)?);
+        for transfer in db::select_transfers(conn)? {
+            let amount = rates
+                .convert(transfer.amount, &old, value, &transfer.date, shift)
+                .ok_or_else(|| {
+                    format!(
+                        "no exchange rate from {} to {} for the transfer on {}",
+                        old, value, transfer.date
+                    )
+                })?;
+            transfers.push((transfer.id, amount));
+        }
+    }
+
+    let n = db::set_home_currency(conn, &old, value, shift, &transfers)?;
+    if old.is_empty() {
+        if shift != 0 {
+            println!("Rescaled {} prices in {}", n, value);
+        }
+    } else {
+        println!("Kept {} prices in {}", n, old);
+        if !transfers.is_empty() {
+            println!("Converted {} transfers to {}", transfers.len(), value);
+        }
+    }
+    println!("home_currency = {}", value);
+
+    Ok(())
+}
+
+fn fatal(err: Box<dyn std::error::Error>) -> ! {
+    eprintln!("shoppingdb: {}", err);
+    std::process::exit(1);
+}
+
 fn main_browse(app: &mut App, key: KeyEvent, conn: &mut Connection) -> Result<()> {
     if key.code == KeyCode::Up {
-        app.table_state
-            .select(navigate_up(&app.items, app.table_state.selected(), 1));
+        window::move_by(app, conn, -1)?;
     } else if key.code == KeyCode::Down {
-        app.table_state
-            .select(navigate_down(&app.items, app.table_state.selected(), 1));
+        window::move_by(app, conn, 1)?;
     } else if key.code == KeyCode::PageUp {
-        app.table_state
-            .select(navigate_up(&app.items, app.table_state.selected(), 10));
+        window::move_by(app, conn, -10)?;
     } else if key.code == KeyCode::PageDown {
-        app.table_state
-            .select(navigate_down(&app.items, app.table_state.selected(), 10));
+        window::move_by(app, conn, 10)?;
     } else if key.code == KeyCode::Home {
-        app.table_state.select(navigate_home(&app.items));
+        window::home(app, conn)?;
     } else if key.code == KeyCode::End {
-        app.table_state.select(navigate_end(&app.items));
-    } else if key.code == KeyCode::F(2) {
-        sort_items(conn)?;
-        app.items = select_items(conn)?;
+        window::end(app, conn)?;
+    } else if key.code == KeyCode::F(2) || key.code == KeyCode::F(3) {
+        if key.code == KeyCode::F(2) {
+            app.sort.column = app.sort.column.next();
+        } else {
+            app.sort.descending = !app.sort.descending;
+        }
+        db::set_setting(conn, "sort", &app.sort.to_setting())?;
+
+        let selected = app.table_state.selected().map(|i| app.items[i].id);
+        window::select(app, conn, selected)?;
     } else if key.code == KeyCode::F(4) {
         if let Some(i) = app.table_state.selected() {
             app.item_template = Some(app.items[i].clone());
+            app.new_item = app.items[i].clone();
             app.transition(AppState::InsertDate);
             app.textarea.insert_str(&app.items[i].date);
         }
+    } else if key.code == KeyCode::F(5) {
+        app.job = Some(Job::Scan(Scan::Stores));
+    } else if key.code == KeyCode::F(6) {
+        app.receipt = Some(DbReceipt {
+            id: 0,
+            date: String::new(),
+            store: String::new(),
+            total: 0,
+            currency: String::new(),
+        });
+        app.receipt_sum = 0;
+        app.new_item.kind = ItemKind::Purchase;
+        app.new_item.original_id = None;
+        app.new_item.tax_rate = None;
+        app.new_item.splits = Vec::new();
+        app.new_item.payer = String::new();
+        app.new_item.shares = Vec::new();
+        app.transition(AppState::ReceiptDate);
+        app.textarea.insert_str(util::today());
     } else if key.code == KeyCode::F(7) {
+        app.new_item.receipt_id = None;
+        app.new_item.kind = ItemKind::Purchase;
+        app.new_item.original_id = None;
+        app.new_item.tax_rate = None;
+        app.new_item.splits = Vec::new();
+        app.new_item.payer = String::new();
+        app.new_item.shares = Vec::new();
         app.transition(AppState::InsertDate);
         app.textarea.insert_str(util::today());
+    } else if key.code == KeyCode::Char('R') || key.code == KeyCode::Char('D') {
+        // Refund or discount on the selected purchase, as a new item
+        // prefilled from it
+        if let Some(i) = app.table_state.selected() {
+            let original = &app.items[i];
+            let mut template = original.clone();
+            template.price = -original.price.abs();
+
+            app.new_item = original.clone();
+            app.new_item.id = 0;
+            app.new_item.kind = if key.code == KeyCode::Char('R') {
+                ItemKind::Refund
+            } else {
+                ItemKind::Discount
+            };
+            app.new_item.original_id = Some(original.original_id.unwrap_or(original.id));
+            app.new_item.receipt_id = None;
+            app.new_item.tags = Vec::new();
+            app.new_item.splits = Vec::new();
+
+            app.item_template = Some(template);
+            app.transition(AppState::InsertDate);
+            app.textarea.insert_str(util::today());
+        }
+    } else if key.code == KeyCode::Char('T') {
+        if let Some(i) = app.table_state.selected() {
+            let name = app.items[i].tax_rate.clone().unwrap_or_default();
+            app.transition(AppState::TaxRate);
+            app.textarea.insert_str(name);
+            app.update_history();
+        }
+    } else if key.code == KeyCode::Char('S') {
+        if let Some(i) = app.table_state.selected() {
+            let item = &app.items[i];
+            let line = split::to_line(app, &item.splits, &item.currency);
+            app.transition(AppState::SplitItem);
+            app.textarea.insert_str(line);
+            app.update_history();
+        }
+    } else if key.code == KeyCode::Right || key.code == KeyCode::Left {
+        if let Some(i) = app.table_state.selected() {
+            let item = &app.items[i];
+            if key.code == KeyCode::Right && !item.splits.is_empty() {
+                app.expanded.insert(item.id);
+            } else {
+                app.expanded.remove(&item.id);
+            }
+        }
+    } else if key.code == KeyCode::Char('P') {
+        if let Some(i) = app.table_state.selected() {
+            let payer = app.items[i].payer.clone();
+            app.transition(AppState::Payer);
+            app.textarea.insert_str(payer);
+            app.update_history();
+        }
+    } else if key.code == KeyCode::Char('B') {
+        app.view_state.select(None);
+        app.job = Some(Job::Scan(Scan::Balances));
+    } else if key.code == KeyCode::Char('V') {
+        app.view_state.select(None);
+        app.job = Some(Job::Scan(Scan::TaxReport(
+            app.filter.clone(),
+            app.tax_yearly,
+        )));
+    } else if key.code == KeyCode::F(9) {
+        if let Some(i) = app.table_state.selected() {
+            let tags = app.items[i].tags.join(" ");
+            app.transition(AppState::EditTags);
+            app.textarea.insert_str(tags);
+        }
+    } else if key.code == KeyCode::Char('u') || key.code == KeyCode::Char('r') {
+        let redo = key.code == KeyCode::Char('r');
+        let before = model::before(app, conn, &journal::next_items(conn, redo)?)?;
+        let (ids, verb, noun) = if redo {
+            (journal::redo(conn)?, "Redid", "redo")
+        } else {
+            (journal::undo(conn)?, "Undid", "undo")
+        };
+
+        app.message_is_error = ids.is_none();
+        app.message = match ids.as_deref() {
+            Some([id]) => format!("{} change to item {}", verb, id),
+            Some(ids) => format!("{} change to {} items", verb, ids.len()),
+            None => format!("Nothing to {}", noun),
+        };
+
+        if let Some(ids) = ids {
+            app.tax_rates = tax::TaxRates::load(conn)?;
+            model::apply(app, conn, before)?;
+            window::select(app, conn, ids.first().copied())?;
+        }
+    } else if key.code == KeyCode::Char('x') {
+        app.view_state.select(None);
+        show_trash(app, conn)?;
+    } else if key.code == KeyCode::Char('c') {
+        app.marked_categories.clear();
+        app.view_state.select(None);
+        app.job = Some(Job::Scan(Scan::Categories));
+    } else if key.code == KeyCode::Char('/') {
+        let line = app.filter.search.clone();
+        app.transition(AppState::Search);
+        app.textarea.insert_str(line);
+    } else if key.code == KeyCode::Char('f') {
+        let line = app.filter.query.as_ref().map(|q| q.text.clone());
+        app.transition(AppState::Filter);
+        app.textarea.insert_str(line.unwrap_or_default());
+    } else if key.code == KeyCode::Char('s') {
+        app.transition(AppState::SaveFilter);
+    } else if key.code == KeyCode::Char('i') {
+        app.transition(AppState::ImportRates);
+    } else if key.code == KeyCode::Char('v') {
+        app.job = Some(Job::Compact);
+    } else if key.code == KeyCode::Char('t') {
+        let line = app.filter.tags.to_line();
+        app.transition(AppState::FilterTags);
+        app.textarea.insert_str(line);
+        app.update_history();
     } else if key.code == KeyCode::F(8) {
         if let Some(i) = app.table_state.selected() {
-            delete_item(conn, app.items[i].id)?;
+            let id = app.items[i].id;
+            let before = model::before(app, conn, &[id])?;
+            db::delete_item(conn, id)?;
 
-            app.items = select_items(conn)?;
-            app.distinct_categories = select_categories(conn)?;
-            app.distinct_descriptions = select_descriptions(conn)?;
-            app.table_state
-                .select(navigate_down(&app.items, app.table_state.selected(), 0));
+            // The cursor moves to the next item, if there is one
+            model::apply(app, conn, before)?;
+            let next = app.table_state.selected().map(|i| app.items[i].id);
+            window::select(app, conn, next)?;
+
+            app.message = format!("Moved item {} to trash, press u to undo", id);
+            app.message_is_error = false;
         }
     }
 
     Ok(())
 }
 
+fn main_receipt_date(app: &mut App, key: KeyEvent) {
+    if key.code == KeyCode::Enter {
+        let line = app.get_text();
+        if line.is_empty() {
+            app.transition(AppState::Browse);
+        } else if let Some(date) = util::parse_date_input(line, &app.date_format) {
+            if let Some(receipt) = &mut app.receipt {
+                receipt.date = date.format("%F").to_string();
+            }
+            app.transition(AppState::ReceiptStore);
+        }
+    } else {
+        app.textarea.input(key);
+    }
+}
+
+fn main_receipt_store(app: &mut App, key: KeyEvent) {
+    if handle_history_input(app, key) {
+        return;
+    }
+
+    if key.code == KeyCode::Enter {
+        let line = String::from(app.get_text());
+        if let Some(receipt) = &mut app.receipt {
+            receipt.store = line;
+        }
+        app.transition(AppState::ReceiptTotal);
+    } else {
+        app.textarea.input(key);
+        app.update_history();
+    }
+}
+
+fn main_receipt_total(app: &mut App, key: KeyEvent) {
+    if key.code == KeyCode::Enter {
+        let line = app.get_text();
+        if let Some((total, currency)) = app.parse_money(line) {
+            if let Some(receipt) = &mut app.receipt {
+                receipt.total = total;
+                receipt.currency = currency;
+
+                app.new_item.date = receipt.date.clone();
+                app.new_item.store = receipt.store.clone();
+                app.new_item.receipt_id = None;
+                model::insert_sorted(&mut app.distinct_stores, &receipt.store);
+            }
+
+            app.transition(AppState::InsertDescription);
+        }
+    } else {
+        app.textarea.input(key);
+    }
+}
+
 fn main_insert_date(app: &mut App, key: KeyEvent) {
     if key.code == KeyCode::Enter {
         let line = app.get_text();
         if line.is_empty() {
             app.transition(AppState::Browse);
-        } else if let Some(date) = util::parse_date(line) {
+        } else if let Some(date) = util::parse_date_input(line, &app.date_format) {
             app.new_item.date = date.format("%F").to_string();
 
             app.transition(AppState::InsertDescription);
@@ -138,7 +569,7 @@ fn main_insert_description(app: &mut App, key: KeyEvent, conn: &Connection) -> R
             if let Some(item) = &app.item_template {
                 app.textarea.insert_str(&item.category);
                 app.update_history();
-            } else if let Ok(autofill) = select_category(conn, &app.new_item.description) {
+            } else if let Ok(autofill) = db::select_category(conn, &app.new_item.description) {
                 app.textarea.insert_str(autofill);
                 app.update_history();
             }
@@ -151,7 +582,7 @@ fn main_insert_description(app: &mut App, key: KeyEvent, conn: &Connection) -> R
     Ok(())
 }
 
-fn main_insert_category(app: &mut App, key: KeyEvent) {
+fn main_insert_category(app: &mut App, key: KeyEvent, conn: &Connection) {
     if handle_history_input(app, key) {
         return;
     }
@@ -160,9 +591,39 @@ fn main_insert_category(app: &mut App, key: KeyEvent) {
         let line = app.get_text();
         app.new_item.category = String::from(line);
 
-        app.transition(AppState::InsertPrice);
+        // Items on a receipt share its store
+        if app.receipt.is_some() {
+            app.transition(AppState::InsertQuantity);
+            return;
+        }
+
+        app.transition(AppState::InsertStore);
+        if let Some(item) = &app.item_template {
+            app.textarea.insert_str(&item.store);
+            app.update_history();
+        } else if let Ok(autofill) = db::select_store(conn, &app.new_item.description) {
+            app.textarea.insert_str(autofill);
+            app.update_history();
+        }
+    } else {
+        app.textarea.input(key);
+        app.update_history();
+    }
+}
+
+fn main_insert_store(app: &mut App, key: KeyEvent) {
+    if handle_history_input(app, key) {
+        return;
+    }
+
+    if key.code == KeyCode::Enter {
+        let line = app.get_text();
+        app.new_item.store = String::from(line);
+
+        app.transition(AppState::InsertQuantity);
         if let Some(item) = &app.item_template {
-            app.textarea.insert_str(util::format_price(item.price));
+            app.textarea
+                .insert_str(quantity::format_quantity(item.quantity, &item.unit));
         }
     } else {
         app.textarea.input(key);
@@ -170,25 +631,97 @@ fn main_insert_category(app: &mut App, key: KeyEvent) {
     }
 }
 
+fn main_insert_quantity(app: &mut App, key: KeyEvent) {
+    if key.code == KeyCode::Enter {
+        let line = app.get_text();
+        if line.is_empty() {
+            app.new_item.quantity = 1.0;
+            app.new_item.unit = String::new();
+        } else if let Some((quantity, unit)) = quantity::parse_quantity(&app.locale, line) {
+            app.new_item.quantity = quantity;
+            app.new_item.unit = unit;
+        } else {
+            return;
+        }
+
+        app.transition(AppState::InsertPrice);
+        if let Some(item) = &app.item_template {
+            app.textarea
+                .insert_str(app.format_money(item.price, &item.currency));
+        }
+    } else {
+        app.textarea.input(key);
+    }
+}
+
 fn main_insert_price(app: &mut App, key: KeyEvent, conn: &Connection) -> Result<()> {
     if key.code == KeyCode::Enter {
         let line = app.get_text();
-        if let Some(price) = util::parse_price(line) {
+        if let Some((price, mut currency)) = app.parse_money(line) {
             let rowid: i64;
-            app.new_item.price = price;
 
-            if let Some(item) = &app.item_template {
-                rowid = item.id;
-                update_item(conn, rowid, &app.new_item)?;
+            if let Some(receipt) = &app.receipt {
+                if currency.is_empty() {
+                    currency = receipt.currency.clone();
+                }
+                if currency == receipt.currency {
+                    app.receipt_sum += price;
+                }
+            }
+
+            // Refunds and discounts take money off, whichever way they
+            // are typed
+            app.new_item.price = if app.new_item.kind == ItemKind::Purchase {
+                price
             } else {
-                rowid = insert_item(conn, &app.new_item)?;
+                -price.abs()
+            };
+            app.new_item.currency = currency;
+
+            // Shares of fixed amounts may not add up to a new price
+            let unshared = household::resolve(app.new_item.price, &app.new_item.shares).is_err();
+            if unshared {
+                app.new_item.shares = Vec::new();
             }
 
-            app.items = select_items(conn)?;
-            app.distinct_categories = select_categories(conn)?;
-            app.distinct_descriptions = select_descriptions(conn)?;
-            app.table_state
-                .select(app.items.iter().position(|item| item.id == rowid));
+            let editing = app
+                .item_template
+                .as_ref()
+                .filter(|item| item.id == app.new_item.id);
+            let before = if let Some(item) = editing {
+                rowid = item.id;
+
+                // Allocations no longer add up once the price changes
+                if !app.new_item.splits.is_empty()
+                    && (app.new_item.price != item.price || app.new_item.currency != item.currency)
+                {
+                    app.new_item.splits = Vec::new();
+                    app.message =
+                        format!("Removed split of item {}, press S to split again", rowid);
+                    app.message_is_error = false;
+                }
+                if unshared {
+                    app.message =
+                        format!("Removed shares of item {}, press P to share again", rowid);
+                    app.message_is_error = false;
+                }
+
+                let before = model::before(app, conn, &[rowid])?;
+                db::update_item(conn, rowid, &app.new_item)?;
+                before
+            } else {
+                // A receipt is saved with its first item, so that one left
+                // without items is not
+                if let Some(receipt) = app.receipt.as_mut().filter(|receipt| receipt.id == 0) {
+                    receipt.id = db::insert_receipt(conn, receipt)?;
+                    app.new_item.receipt_id = Some(receipt.id);
+                }
+                rowid = db::insert_item(conn, &app.new_item)?;
+                vec![model::Before::new_item(rowid)]
+            };
+
+            model::apply(app, conn, before)?;
+            window::select(app, conn, Some(rowid))?;
 
             if app.item_template.is_some() {
                 app.transition(AppState::Browse);
@@ -203,6 +736,620 @@ fn main_insert_price(app: &mut App, key: KeyEvent, conn: &Connection) -> Result<
     Ok(())
 }
 
+fn main_edit_tags(app: &mut App, key: KeyEvent, conn: &mut Connection) -> Result<()> {
+    if handle_history_input(app, key) {
+        return Ok(());
+    }
+
+    if key.code == KeyCode::Enter {
+        if let Some(i) = app.table_state.selected() {
+            let id = app.items[i].id;
+            let before = model::before(app, conn, &[id])?;
+            db::set_item_tags(conn, id, &util::split_tags(app.get_text()))?;
+
+            model::apply(app, conn, before)?;
+            window::select(app, conn, Some(id))?;
+        }
+        app.transition(AppState::Browse);
+    } else {
+        app.textarea.input(key);
+        app.update_history();
+    }
+
+    Ok(())
+}
+
+fn main_filter_tags(app: &mut App, key: KeyEvent) {
+    if handle_history_input(app, key) {
+        return;
+    }
+
+    if key.code == KeyCode::Enter {
+        let mut filter = app.filter.clone();
+        filter.tags = TagFilter::parse(app.get_text());
+
+        app.job = Some(model::reload_job(app, filter));
+        app.transition(AppState::Browse);
+    } else {
+        app.textarea.input(key);
+        app.update_history();
+    }
+}
+
+fn main_filter(app: &mut App, key: KeyEvent) {
+    if handle_history_input(app, key) {
+        return;
+    }
+
+    if key.code == KeyCode::Enter {
+        let line = app.get_text();
+        let query = if line.is_empty() {
+            None
+        } else {
+            match filter::parse(app, line) {
+                Ok(query) => Some(query),
+                Err(_) => return,
+            }
+        };
+
+        let mut filter = app.filter.clone();
+        filter.query = query;
+        app.job = Some(model::reload_job(app, filter));
+        app.transition(AppState::Browse);
+    } else {
+        app.textarea.input(key);
+        app.update_history();
+    }
+}
+
+// Save the active filter under a name, or forget the name if there is no
+// active filter.
+fn main_save_filter(app: &mut App, key: KeyEvent, conn: &Connection) -> Result<()> {
+    if handle_history_input(app, key) {
+        return Ok(());
+    }
+
+    if key.code == KeyCode::Enter {
+        let name = String::from(app.get_text());
+        if name.contains(|c: char| c.is_whitespace() || c == '@' || c == '"') {
+            return Ok(());
+        }
+
+        if !name.is_empty() {
+            let query = app.filter.query.as_ref().map_or("", |q| q.text.as_str());
+            db::save_filter(conn, &name, query)?;
+            app.saved_filters = db::select_filters(conn)?;
+
+            app.message = if query.is_empty() {
+                format!("Deleted saved filter @{}", name)
+            } else {
+                format!("Saved filter as @{}", name)
+            };
+            app.message_is_error = false;
+        }
+        app.transition(AppState::Browse);
+    } else {
+        app.textarea.input(key);
+        app.update_history();
+    }
+
+    Ok(())
+}
+
+// Filter the table as the query is typed.  An invalid query, e.g. with
+// unbalanced quotes, leaves the previous results in place.
+fn main_search(app: &mut App, key: KeyEvent, conn: &Connection) -> Result<()> {
+    if key.code == KeyCode::Enter {
+        app.transition(AppState::Browse);
+        return Ok(());
+    }
+
+    app.textarea.input(key);
+
+    let selected = app.table_state.selected().map(|i| app.items[i].id);
+    let previous = app.filter.search.clone();
+    app.filter.search = String::from(app.get_text());
+
+    // Invalid queries fail on counting, before anything is loaded
+    app.search_error = window::reload(app, conn, selected).is_err();
+    if app.search_error {
+        app.filter.search = previous;
+    }
+
+    Ok(())
+}
+
+fn main_stores(app: &mut App, key: KeyEvent) {
+    if key.code == KeyCode::Up {
+        app.view_state
+            .select(navigate_up(&app.store_stats, app.view_state.selected(), 1));
+    } else if key.code == KeyCode::Down {
+        app.view_state.select(navigate_down(
+            &app.store_stats,
+            app.view_state.selected(),
+            1,
+        ));
+    }
+}
+
+fn main_categories(app: &mut App, key: KeyEvent) {
+    if key.code == KeyCode::Up {
+        app.view_state.select(navigate_up(
+            &app.category_tree,
+            app.view_state.selected(),
+            1,
+        ));
+    } else if key.code == KeyCode::Down {
+        app.view_state.select(navigate_down(
+            &app.category_tree,
+            app.view_state.selected(),
+            1,
+        ));
+    } else if key.code == KeyCode::PageUp {
+        app.view_state.select(navigate_up(
+            &app.category_tree,
+            app.view_state.selected(),
+            10,
+        ));
+    } else if key.code == KeyCode::PageDown {
+        app.view_state.select(navigate_down(
+            &app.category_tree,
+            app.view_state.selected(),
+            10,
+        ));
+    } else if let Some(i) = app.view_state.selected() {
+        let node = &app.category_tree[i];
+
+        if key.code == KeyCode::Char(' ') {
+            if node.count > 0 && !app.marked_categories.remove(&node.path) {
+                app.marked_categories.insert(node.path.clone());
+            }
+            app.view_state
+                .select(navigate_down(&app.category_tree, Some(i), 1));
+        } else if key.code == KeyCode::Char('r') {
+            let path = node.path.clone();
+            app.transition(AppState::RenameCategory);
+            app.textarea.insert_str(path);
+            app.update_history();
+        } else if key.code == KeyCode::Char('t') {
+            let name = app.tax_rates.categories.get(&node.path).cloned();
+            app.transition(AppState::CategoryTax);
+            app.textarea.insert_str(name.unwrap_or_default());
+            app.update_history();
+        } else if key.code == KeyCode::Char('m') {
+            if app.marked_categories.is_empty() && node.count > 0 {
+                app.marked_categories.insert(node.path.clone());
+            }
+            if !app.marked_categories.is_empty() {
+                app.transition(AppState::MergeCategories);
+                app.update_history();
+            }
+        }
+    }
+}
+
+fn main_trash(app: &mut App, key: KeyEvent, conn: &mut Connection) -> Result<()> {
+    if key.code == KeyCode::Up {
+        app.view_state
+            .select(navigate_up(&app.trash, app.view_state.selected(), 1));
+    } else if key.code == KeyCode::Down {
+        app.view_state
+            .select(navigate_down(&app.trash, app.view_state.selected(), 1));
+    } else if key.code == KeyCode::PageUp {
+        app.view_state
+            .select(navigate_up(&app.trash, app.view_state.selected(), 10));
+    } else if key.code == KeyCode::PageDown {
+        app.view_state
+            .select(navigate_down(&app.trash, app.view_state.selected(), 10));
+    } else if key.code == KeyCode::Char('e') {
+        app.transition(AppState::EmptyTrash);
+    } else if let Some(i) = app.view_state.selected() {
+        let id = app.trash[i].id;
+
+        if key.code == KeyCode::Char('r') {
+            let before = model::before(app, conn, &[id])?;
+            db::restore_item(conn, id)?;
+            model::apply(app, conn, before)?;
+            show_trash(app, conn)?;
+        } else if key.code == KeyCode::F(8) {
+            app.transition(AppState::PurgeItem);
+        }
+    }
+
+    Ok(())
+}
+
+fn main_purge_item(app: &mut App, key: KeyEvent, conn: &mut Connection) -> Result<()> {
+    if key.code == KeyCode::Enter {
+        if let Some(i) = app.view_state.selected().filter(|_| app.get_text() == "y") {
+            db::purge_items(conn, Some(app.trash[i].id), None, &mut |_, _| true)?;
+        }
+        show_trash(app, conn)?;
+    } else {
+        app.textarea.input(key);
+    }
+
+    Ok(())
+}
+
+fn main_empty_trash(app: &mut App, key: KeyEvent) {
+    if key.code == KeyCode::Enter {
+        if let Ok(days) = app.get_text().parse::<u32>() {
+            app.job = Some(Job::EmptyTrash(days));
+            app.transition(AppState::Trash);
+        }
+    } else {
+        app.textarea.input(key);
+    }
+}
+
+fn main_import_rates(app: &mut App, key: KeyEvent) {
+    if key.code == KeyCode::Enter {
+        let line = app.get_text();
+        if !line.is_empty() {
+            app.job = Some(Job::ImportRates(std::path::PathBuf::from(line)));
+        }
+        app.transition(AppState::Browse);
+    } else {
+        app.textarea.input(key);
+    }
+}
+
+fn show_trash(app: &mut App, conn: &Connection) -> Result<()> {
+    app.trash = db::select_trash(conn)?;
+    app.transition(AppState::Trash);
+
+    let selected = app.view_state.selected();
+    app.view_state.select(
+        selected
+            .map(|i| navigate_down(&app.trash, Some(i), 0))
+            .unwrap_or_else(|| navigate_home(&app.trash)),
+    );
+
+    Ok(())
+}
+
+fn main_rename_category(app: &mut App, key: KeyEvent) {
+    if handle_history_input(app, key) {
+        return;
+    }
+
+    if key.code == KeyCode::Enter {
+        if let Some(i) = app.view_state.selected() {
+            app.job = Some(Job::RenameCategory {
+                old: app.category_tree[i].path.clone(),
+                new: String::from(app.get_text()),
+                separator: app.category_separator.clone(),
+            });
+            app.marked_categories.clear();
+        }
+        app.transition(AppState::Categories);
+    } else {
+        app.textarea.input(key);
+        app.update_history();
+    }
+}
+
+fn main_merge_categories(app: &mut App, key: KeyEvent) {
+    if handle_history_input(app, key) {
+        return;
+    }
+
+    if key.code == KeyCode::Enter {
+        app.job = Some(Job::MergeCategories {
+            categories: app.marked_categories.iter().cloned().collect(),
+            into: String::from(app.get_text()),
+        });
+        app.marked_categories.clear();
+        app.transition(AppState::Categories);
+    } else {
+        app.textarea.input(key);
+        app.update_history();
+    }
+}
+
+// Override the tax rate of the selected item, or go back to that of its
+// category if left empty.
+fn main_tax_rate(app: &mut App, key: KeyEvent, conn: &mut Connection) -> Result<()> {
+    if handle_history_input(app, key) {
+        return Ok(());
+    }
+
+    if key.code == KeyCode::Enter {
+        let name = String::from(app.get_text());
+        if !name.is_empty() && !app.tax_rates.rates.contains_key(&name) {
+            return Ok(());
+        }
+
+        if let Some(i) = app.table_state.selected() {
+            let mut item = app.items[i].clone();
+            item.tax_rate = Some(name).filter(|name| !name.is_empty());
+
+            let before = model::before(app, conn, &[item.id])?;
+            db::update_item(conn, item.id, &item)?;
+            model::apply(app, conn, before)?;
+            window::select(app, conn, Some(item.id))?;
+        }
+        app.transition(AppState::Browse);
+    } else {
+        app.textarea.input(key);
+        app.update_history();
+    }
+
+    Ok(())
+}
+
+// Split the selected item across categories, or undo the split if left
+// empty.  Nothing is saved until the allocations add up to the price.
+fn main_split_item(app: &mut App, key: KeyEvent, conn: &mut Connection) -> Result<()> {
+    if handle_history_input(app, key) {
+        return Ok(());
+    }
+
+    if key.code == KeyCode::Enter {
+        if let Some(i) = app.table_state.selected() {
+            let mut item = app.items[i].clone();
+            item.splits = match split::parse(app, app.get_text(), item.price, &item.currency) {
+                Ok(splits) => splits,
+                Err(_) => return Ok(()),
+            };
+
+            let before = model::before(app, conn, &[item.id])?;
+            db::update_item(conn, item.id, &item)?;
+            model::apply(app, conn, before)?;
+            window::select(app, conn, Some(item.id))?;
+            app.expanded.insert(item.id);
+        }
+        app.transition(AppState::Browse);
+    } else {
+        app.textarea.input(key);
+        app.update_history();
+    }
+
+    Ok(())
+}
+
+// Record who paid for the selected item, then ask how it is shared.  An
+// empty payer removes both.
+fn main_payer(app: &mut App, key: KeyEvent, conn: &mut Connection) -> Result<()> {
+    if handle_history_input(app, key) {
+        return Ok(());
+    }
+
+    if key.code == KeyCode::Enter {
+        let i = match app.table_state.selected() {
+            Some(i) => i,
+            None => {
+                app.transition(AppState::Browse);
+                return Ok(());
+            }
+        };
+        let payer = String::from(app.get_text());
+
+        if payer.is_empty() {
+            let mut item = app.items[i].clone();
+            item.payer = String::new();
+            item.shares = Vec::new();
+
+            let before = model::before(app, conn, &[item.id])?;
+            db::update_item(conn, item.id, &item)?;
+            model::apply(app, conn, before)?;
+            window::select(app, conn, Some(item.id))?;
+            app.transition(AppState::Browse);
+            return Ok(());
+        }
+
+        // Offer the shares as they are, or else equal parts for everyone
+        let item = &app.items[i];
+        let line = if item.shares.is_empty() {
+            let mut members = app.distinct_members.clone();
+            model::insert_sorted(&mut members, &payer);
+            members.join("; ")
+        } else {
+            household::to_line(app, &item.shares, &item.currency)
+        };
+
+        app.payer = payer;
+        app.transition(AppState::Shares);
+        app.textarea.insert_str(line);
+        app.update_history();
+    } else {
+        app.textarea.input(key);
+        app.update_history();
+    }
+
+    Ok(())
+}
+
+fn main_shares(app: &mut App, key: KeyEvent, conn: &mut Connection) -> Result<()> {
+    if handle_history_input(app, key) {
+        return Ok(());
+    }
+
+    if key.code == KeyCode::Enter {
+        if let Some(i) = app.table_state.selected() {
+            let mut item = app.items[i].clone();
+            item.shares = match household::parse(app, app.get_text(), item.price, &item.currency) {
+                Ok(shares) => shares,
+                Err(_) => return Ok(()),
+            };
+            item.payer = std::mem::take(&mut app.payer);
+
+            let before = model::before(app, conn, &[item.id])?;
+            db::update_item(conn, item.id, &item)?;
+            model::apply(app, conn, before)?;
+            window::select(app, conn, Some(item.id))?;
+        }
+        app.transition(AppState::Browse);
+    } else {
+        app.textarea.input(key);
+        app.update_history();
+    }
+
+    Ok(())
+}
+
+fn main_balances(app: &mut App, key: KeyEvent, conn: &mut Connection) -> Result<()> {
+    if key.code == KeyCode::Up {
+        app.view_state
+            .select(navigate_up(&app.transfers, app.view_state.selected(), 1));
+    } else if key.code == KeyCode::Down {
+        app.view_state
+            .select(navigate_down(&app.transfers, app.view_state.selected(), 1));
+    } else if key.code == KeyCode::PageUp {
+        app.view_state
+            .select(navigate_up(&app.transfers, app.view_state.selected(), 10));
+    } else if key.code == KeyCode::PageDown {
+        app.view_state
+            .select(navigate_down(&app.transfers, app.view_state.selected(), 10));
+    } else if key.code == KeyCode::Char('s') {
+        if !app.settlement.is_empty() {
+            app.transition(AppState::SettleUp);
+            app.textarea.insert_str(util::today());
+        }
+    } else if key.code == KeyCode::F(8) {
+        if let Some(i) = app.view_state.selected() {
+            db::delete_transfer(conn, app.transfers[i].id)?;
+            app.job = Some(Job::Scan(Scan::Balances));
+        }
+    }
+
+    Ok(())
+}
+
+// Record the transfers that settle all balances, on the date typed.
+fn main_settle_up(app: &mut App, key: KeyEvent, conn: &mut Connection) -> Result<()> {
+    if key.code == KeyCode::Enter {
+        if let Some(date) = util::parse_date_input(app.get_text(), &app.date_format) {
+            let mut transfers = app.settlement.clone();
+            for transfer in &mut transfers {
+                transfer.date = date.format("%F").to_string();
+            }
+            db::insert_transfers(conn, &transfers)?;
+
+            app.message = format!("Recorded {} transfers to settle up", transfers.len());
+            app.message_is_error = false;
+            app.transition(AppState::Balances);
+            app.job = Some(Job::Scan(Scan::Balances));
+        }
+    } else {
+        app.textarea.input(key);
+    }
+
+    Ok(())
+}
+
+// Enter a statistics view with the items the worker has read for it.
+fn show_scan(app: &mut App, stats: Stats) {
+    match stats {
+        Stats::Stores(store_stats, cheapest) => show_stores(app, store_stats, cheapest),
+        Stats::Categories(tree) => show_categories(app, tree),
+        Stats::TaxReport(report) => show_tax_report(app, report),
+        Stats::Balances(balances, transfers) => show_balances(app, balances, transfers),
+    }
+}
+
+// Enter the Stores view, or refresh it in place, keeping the cursor where
+// it was.
+fn show_stores(app: &mut App, store_stats: Vec<StoreStats>, cheapest: Vec<Cheapest>) {
+    let refresh = app.state == AppState::Stores;
+    app.store_stats = store_stats;
+    app.cheapest = cheapest;
+    app.transition(AppState::Stores);
+
+    let selected = app.view_state.selected().filter(|_| refresh);
+    match selected {
+        Some(i) if i < app.store_stats.len() => (),
+        _ => app.view_state.select(navigate_home(&app.store_stats)),
+    }
+}
+
+// Enter the Balances view, with what every member owes or is owed.
+fn show_balances(app: &mut App, balances: Vec<Balance>, transfers: Vec<Transfer>) {
+    app.transfers = transfers;
+    app.balances = balances;
+    app.settlement = household::settle(&app.balances);
+    app.transition(AppState::Balances);
+
+    let selected = app.view_state.selected();
+    app.view_state.select(
+        selected
+            .map(|i| navigate_down(&app.transfers, Some(i), 0))
+            .unwrap_or_else(|| navigate_home(&app.transfers)),
+    );
+}
+
+// Set the tax rate of the selected category, or remove it if left empty.
+fn main_category_tax(app: &mut App, key: KeyEvent, conn: &mut Connection) -> Result<()> {
+    if handle_history_input(app, key) {
+        return Ok(());
+    }
+
+    if key.code == KeyCode::Enter {
+        let name = app.get_text();
+        if !name.is_empty() && !app.tax_rates.rates.contains_key(name) {
+            return Ok(());
+        }
+
+        if let Some(i) = app.view_state.selected() {
+            db::set_category_tax(conn, &app.category_tree[i].path, name)?;
+            app.tax_rates = tax::TaxRates::load(conn)?;
+        }
+        app.transition(AppState::Categories);
+    } else {
+        app.textarea.input(key);
+        app.update_history();
+    }
+
+    Ok(())
+}
+
+fn main_tax_report(app: &mut App, key: KeyEvent) {
+    if key.code == KeyCode::Up {
+        app.view_state
+            .select(navigate_up(&app.tax_report, app.view_state.selected(), 1));
+    } else if key.code == KeyCode::Down {
+        app.view_state
+            .select(navigate_down(&app.tax_report, app.view_state.selected(), 1));
+    } else if key.code == KeyCode::PageUp {
+        app.view_state
+            .select(navigate_up(&app.tax_report, app.view_state.selected(), 10));
+    } else if key.code == KeyCode::PageDown {
+        app.view_state.select(navigate_down(
+            &app.tax_report,
+            app.view_state.selected(),
+            10,
+        ));
+    } else if key.code == KeyCode::Char('p') {
+        app.tax_yearly = !app.tax_yearly;
+        app.view_state.select(None);
+        app.job = Some(Job::Scan(Scan::TaxReport(
+            app.filter.clone(),
+            app.tax_yearly,
+        )));
+    }
+}
+
+// Enter the TaxReport view, over the items that match the filter.
+fn show_tax_report(app: &mut App, report: Vec<TaxRow>) {
+    app.tax_report = report;
+    app.transition(AppState::TaxReport);
+    app.view_state.select(navigate_home(&app.tax_report));
+}
+
+// Enter the Categories view with fresh totals, keeping the cursor in place.
+fn show_categories(app: &mut App, tree: Vec<CategoryNode>) {
+    app.category_tree = tree;
+    app.transition(AppState::Categories);
+
+    let selected = app.view_state.selected();
+    app.view_state.select(
+        selected
+            .map(|i| navigate_down(&app.category_tree, Some(i), 0))
+            .unwrap_or_else(|| navigate_home(&app.category_tree)),
+    );
+}
+
 fn handle_history_input(app: &mut App, key: KeyEvent) -> bool {
     if key.code == KeyCode::Up {
         app.list_state
@@ -223,7 +1370,14 @@ fn handle_history_input(app: &mut App, key: KeyEvent) -> bool {
     } else if key.code == KeyCode::Tab {
         if let Some(i) = app.list_state.selected() {
             if i < app.history.len() {
-                let text = String::from(&app.history[i]);
+                // Replace only the word being completed
+                let prefix = app.get_completion_prefix().len();
+                let line = &app.textarea.lines()[0];
+                let text = if app.get_text().len() == prefix {
+                    String::from(&app.history[i])
+                } else {
+                    format!("{}{}", &line[..line.len() - prefix], app.history[i])
+                };
                 app.textarea.move_cursor(CursorMove::Head);
                 app.textarea.delete_line_by_end();
                 app.textarea.insert_str(text);
@@ -237,7 +1391,7 @@ fn handle_history_input(app: &mut App, key: KeyEvent) -> bool {
     true
 }
 
-fn navigate_up<T>(list: &Vec<T>, selected: Option<usize>, delta: usize) -> Option<usize> {
+fn navigate_up<T>(list: &[T], selected: Option<usize>, delta: usize) -> Option<usize> {
     if list.is_empty() {
         None
     } else {
@@ -254,7 +1408,7 @@ fn navigate_up<T>(list: &Vec<T>, selected: Option<usize>, delta: usize) -> Optio
     }
 }
 
-fn navigate_down<T>(list: &Vec<T>, selected: Option<usize>, delta: usize) -> Option<usize> {
+fn navigate_down<T>(list: &[T], selected: Option<usize>, delta: usize) -> Option<usize> {
     if list.is_empty() {
         None
     } else {
@@ -271,7 +1425,7 @@ fn navigate_down<T>(list: &Vec<T>, selected: Option<usize>, delta: usize) -> Opt
     }
 }
 
-fn navigate_home<T>(list: &Vec<T>) -> Option<usize> {
+fn navigate_home<T>(list: &[T]) -> Option<usize> {
     if list.is_empty() {
         None
     } else {
@@ -279,134 +1433,10 @@ fn navigate_home<T>(list: &Vec<T>) -> Option<usize> {
     }
 }
 
-fn navigate_end<T>(list: &Vec<T>) -> Option<usize> {
+fn navigate_end<T>(list: &[T]) -> Option<usize> {
     if list.is_empty() {
         None
     } else {
         Some(list.len() - 1)
     }
 }
-
-fn create_database(conn: &Connection) -> Result<()> {
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS items (
-             id INTEGER PRIMARY KEY,
-             date TEXT NOT NULL,
-             category TEXT NOT NULL,
-             description TEXT NOT NULL,
-             price INTEGER NOT NULL
-         )",
-        (),
-    )?;
-
-    Ok(())
-}
-
-fn insert_item(conn: &Connection, item: &DbItem) -> Result<i64> {
-    let mut stmt = conn
-        .prepare("INSERT INTO items(date, category, description, price) values (?1, ?2, ?3, ?4)")?;
-
-    stmt.execute(params![
-        item.date,
-        item.category,
-        item.description,
-        item.price
-    ])?;
-
-    Ok(conn.last_insert_rowid())
-}
-
-fn update_item(conn: &Connection, id: i64, item: &DbItem) -> Result<()> {
-    conn.execute(
-        "UPDATE items SET date=?1, category=?2, description=?3, price=?4 WHERE id=?5",
-        params![item.date, item.category, item.description, item.price, id],
-    )?;
-
-    Ok(())
-}
-
-fn delete_item(conn: &Connection, id: i64) -> Result<()> {
-    conn.execute("DELETE FROM items WHERE id=?1", params![id])?;
-
-    Ok(())
-}
-
-fn sort_items(conn: &mut Connection) -> Result<()> {
-    let tx = conn.transaction()?;
-
-    tx.execute("ALTER TABLE items RENAME TO items2", ())?;
-
-    tx.execute(
-        "CREATE TABLE IF NOT EXISTS items (
-             id INTEGER PRIMARY KEY,
-             date TEXT NOT NULL,
-             category TEXT NOT NULL,
-             description TEXT NOT NULL,
-             price INTEGER NOT NULL
-        )",
-        (),
-    )?;
-
-    tx.execute(
-        "INSERT INTO items(date, category, description, price)
-            SELECT date, category, description, price
-            FROM items2
-            ORDER BY date",
-        (),
-    )?;
-
-    tx.execute("DROP TABLE items2", ())?;
-
-    tx.commit()?;
-
-    conn.execute("VACUUM", ())?;
-
-    Ok(())
-}
-
-fn select_items(conn: &Connection) -> Result<Vec<DbItem>> {
-    let mut stmt = conn.prepare("SELECT id, date, category, description, price FROM items")?;
-    let iter = stmt.query_map([], |row| {
-        Ok(DbItem {
-            id: row.get(0)?,
-            date: row.get(1)?,
-            category: row.get(2)?,
-            description: row.get(3)?,
-            price: row.get(4)?,
-        })
-    })?;
-
-    Ok(iter.map(|item| item.unwrap()).collect())
-}
-
-fn select_category(conn: &Connection, description: &str) -> Result<String> {
-    conn.query_row(
-        "SELECT category FROM items WHERE description=?1 LIMIT 1",
-        params![description],
-        |row| row.get(0),
-    )
-}
-
-fn select_categories(conn: &Connection) -> Result<Vec<String>> {
-    let mut stmt = conn.prepare("SELECT DISTINCT category FROM items ORDER BY category")?;
-    let mut rows = stmt.query([])?;
-    let mut categories = Vec::new();
-
-    while let Some(row) = rows.next()? {
-        categories.push(row.get(0)?);
-    }
-
-    Ok(categories)
-}
-
-fn select_descriptions(conn: &Connection) -> Result<Vec<String>> {
-    let mut stmt = conn.prepare("SELECT DISTINCT description FROM items ORDER BY description")?;
-    let mut rows = stmt.query([])?;
-    let mut descriptions = Vec::new();
-
-    while let Some(row) = rows.next()? {
-        descriptions.push(row.get(0)?);
-    }
-
-    Ok(descriptions)
-}
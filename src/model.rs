@@ -0,0 +1,389 @@
+use crate::app::{App, DbItem, ItemFilter, SortOrder};
+use crate::tax::TaxRates;
+use crate::window::Window;
+use crate::worker::Job;
+use crate::{currency, db, window};
+use rusqlite::{Connection, Result};
+
+// App keeps copies of what it shows from the database: a window of the
+// items (see window.rs), their count and total, and the distinct values
+// offered for completion.  Changes made here are applied to the copies in
+// place, so that editing does not read the whole database again.  Only a
+// change of filter, or one made by another program or the worker, noticed
+// through PRAGMA data_version, causes everything to be reloaded, which the
+// worker does.
+
+// An item as it was before a change.
+pub struct Before {
+    id: i64,
+
+    // The item, which may be in the trash, or None if it did not exist.
+    item: Option<DbItem>,
+
+    // Whether it matched the filter, i.e. was counted in the table.
+    shown: bool,
+}
+
+impl Before {
+    // An item that is about to be inserted.
+    pub fn new_item(id: i64) -> Before {
+        Before {
+            id,
+            item: None,
+            shown: false,
+        }
+    }
+}
+
+// Remember the items before changing them.
+pub fn before(app: &App, conn: &Connection, ids: &[i64]) -> Result<Vec<Before>> {
+    let mut list = Vec::new();
+
+    for &id in ids {
+        list.push(Before {
+            id,
+            item: db::select_item(conn, id)?,
+            shown: db::select_filtered_item(conn, &app.filter, id)?.is_some(),
+        });
+    }
+
+    Ok(list)
+}
+
+// Bring the copies up to date with the items after a change.
+pub fn apply(app: &mut App, conn: &Connection, changes: Vec<Before>) -> Result<()> {
+    for before in changes {
+        let after = db::select_filtered_item(conn, &app.filter, before.id)?;
+
+        if let Some(item) = before.item.as_ref().filter(|_| before.shown) {
+            match app.home_price(item) {
+                Some(price) => app.total -= price,
+                None => app.unconverted = app.unconverted.saturating_sub(1),
+            }
+            window::remove(app, item);
+        }
+
+        if let Some(item) = after {
+            match app.home_price(&item) {
+                Some(price) => app.total += price,
+                None => app.unconverted += 1,
+            }
+            window::insert(app, item);
+        }
+
+        let current = db::select_item(conn, before.id)?;
+        update_completions(app, conn, before.item.as_ref(), current.as_ref())?;
+    }
+
+    Ok(())
+}
+
+// Add values that are new to the completion lists, and drop those that no
+// item outside the trash uses any more.  Stores, tags and members are never
+// dropped, as they are kept in tables of their own.
+fn update_completions(
+    app: &mut App,
+    conn: &Connection,
+    before: Option<&DbItem>,
+    after: Option<&DbItem>,
+) -> Result<()> {
+    let before = before.filter(|item| item.deleted_at.is_none());
+    let after = after.filter(|item| item.deleted_at.is_none());
+
+    if let Some(item) = after {
+        insert_sorted(&mut app.distinct_categories, &item.category);
+        for split in &item.splits {
+            insert_sorted(&mut app.distinct_categories, &split.category);
+        }
+        insert_sorted(&mut app.distinct_descriptions, &item.description);
+        insert_sorted(&mut app.distinct_stores, &item.store);
+        insert_sorted(&mut app.distinct_members, &item.payer);
+        for share in &item.shares {
+            insert_sorted(&mut app.distinct_members, &share.member);
+        }
+        for tag in &item.tags {
+            insert_sorted(&mut app.distinct_tags, tag);
+        }
+    }
+
+    if let Some(item) = before {
+        let categories =
+            std::iter::once(&item.category).chain(item.splits.iter().map(|split| &split.category));
+        for category in categories {
+            let kept = after.is_some_and(|after| {
+                after.category == *category
+                    || after.splits.iter().any(|split| split.category == *category)
+            });
+            if !kept && !db::is_used(conn, "category", category)? {
+                remove_sorted(&mut app.distinct_categories, category);
+            }
+        }
+        if after.is_none_or(|after| after.description != item.description)
+            && !db::is_used(conn, "description", &item.description)?
+        {
+            remove_sorted(&mut app.distinct_descriptions, &item.description);
+        }
+    }
+
+    Ok(())
+}
+
+pub fn insert_sorted(list: &mut Vec<String>, value: &str) {
+    if value.is_empty() {
+        return;
+    }
+    if let Err(i) = list.binary_search_by(|li| li.as_str().cmp(value)) {
+        list.insert(i, String::from(value));
+    }
+}
+
+fn remove_sorted(list: &mut Vec<String>, value: &str) {
+    if let Ok(i) = list.binary_search_by(|li| li.as_str().cmp(value)) {
+        list.remove(i);
+    }
+}
+
+// Everything reload reads, for the sort order and filter it was read with,
+// which the worker reads when the filter changes.
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    pub sort: SortOrder,
+    pub filter: ItemFilter,
+    window: Window,
+    categories: Vec<String>,
+    descriptions: Vec<String>,
+    stores: Vec<String>,
+    members: Vec<String>,
+    tags: Vec<String>,
+}
+
+// Read everything again, keeping the cursor on the same item.
+pub fn reload(app: &mut App, conn: &Connection) -> Result<()> {
+    app.data_version = db::data_version(conn)?;
+
+    let selected = app.table_state.selected().map(|i| app.items[i].id);
+    let snapshot = read(conn, app.sort, &app.filter, selected)?;
+    show(app, snapshot);
+
+    Ok(())
+}
+
+pub fn read(
+    conn: &Connection,
+    sort: SortOrder,
+    filter: &ItemFilter,
+    id: Option<i64>,
+) -> Result<Snapshot> {
+    Ok(Snapshot {
+        sort,
+        filter: filter.clone(),
+        window: window::read(conn, sort, filter, id)?,
+        categories: db::select_categories(conn)?,
+        descriptions: db::select_descriptions(conn)?,
+        stores: db::select_stores(conn)?,
+        members: db::select_members(conn)?,
+        tags: db::select_tags(conn)?,
+    })
+}
+
+pub fn show(app: &mut App, snapshot: Snapshot) {
+    app.sort = snapshot.sort;
+    app.filter = snapshot.filter;
+    window::show(app, snapshot.window);
+    app.distinct_categories = snapshot.categories;
+    app.distinct_descriptions = snapshot.descriptions;
+    app.distinct_stores = snapshot.stores;
+    app.distinct_members = snapshot.members;
+    app.distinct_tags = snapshot.tags;
+}
+
+// Settings that amounts are converted and statistics worked out with,
+// which the worker reads for itself.
+pub fn load_settings(app: &mut App, conn: &Connection) -> Result<()> {
+    app.home_currency = db::get_setting(conn, "home_currency")?.unwrap_or_default();
+    if let Some(separator) = db::get_setting(conn, "category_separator")? {
+        app.category_separator = separator;
+    }
+    if let Some(setting) = db::get_setting(conn, "minor_units")? {
+        app.minor_units = currency::MinorUnits::parse(&setting).unwrap_or_default();
+    }
+    app.rates = currency::Rates::new(db::select_rates(conn)?);
+    app.tax_rates = TaxRates::load(conn)?;
+
+    Ok(())
+}
+
+// Job that reads everything again with the given filter, keeping the
+// cursor on the same item.  The filter takes effect once it has been read.
+pub fn reload_job(app: &App, filter: ItemFilter) -> Job {
+    Job::Reload {
+        sort: app.sort,
+        filter,
+        id: app.table_state.selected().map(|i| app.items[i].id),
+    }
+}
+
+// Reload if another program, or the worker, has changed the database since
+// it was read.  The small tables are read here, and the items on the worker.
+pub fn check_version(app: &mut App, conn: &Connection) -> Result<()> {
+    let version = db::data_version(conn)?;
+    if version == app.data_version {
+        return Ok(());
+    }
+    app.data_version = version;
+
+    app.rates = currency::Rates::new(db::select_rates(conn)?);
+    app.saved_filters = db::select_filters(conn)?;
+    app.tax_rates = TaxRates::load(conn)?;
+    app.job = Some(reload_job(app, app.filter.clone()));
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(app: &App, date: &str, category: &str, description: &str, price: i64) -> DbItem {
+        let mut item = app.new_item.clone();
+        item.date = String::from(date);
+        item.category = String::from(category);
+        item.description = String::from(description);
+        item.price = price;
+        item
+    }
+
+    // Change the item with the given id in the database and in the copies.
+    fn edit(app: &mut App, conn: &Connection, id: i64, change: impl FnOnce(&mut DbItem)) {
+        let changes = before(app, conn, &[id]).unwrap();
+        let mut item = db::select_item(conn, id).unwrap().unwrap();
+        change(&mut item);
+        db::update_item(conn, id, &item).unwrap();
+        apply(app, conn, changes).unwrap();
+    }
+
+    // The copies are what reading everything again gives, with the cursor
+    // on the same item and the window a run of items in sort order.
+    fn assert_fresh(app: &App, conn: &Connection) {
+        let selected = app.table_state.selected().map(|i| app.items[i].id);
+        let mut fresh = App::new();
+        show(
+            &mut fresh,
+            read(conn, app.sort, &app.filter, selected).unwrap(),
+        );
+
+        assert_eq!(app.item_count, fresh.item_count);
+        assert_eq!(
+            (app.total, app.unconverted),
+            (fresh.total, fresh.unconverted)
+        );
+        assert_eq!(app.distinct_categories, fresh.distinct_categories);
+        assert_eq!(app.distinct_descriptions, fresh.distinct_descriptions);
+        assert_eq!(app.distinct_stores, fresh.distinct_stores);
+        assert_eq!(app.distinct_tags, fresh.distinct_tags);
+
+        let all: Vec<i64> = db::select_page(conn, app.sort, &app.filter, None, true, 10000)
+            .unwrap()
+            .iter()
+            .map(|item| item.id)
+            .collect();
+        let window: Vec<i64> = app.items.iter().map(|item| item.id).collect();
+        let offset = app.items_offset;
+        assert_eq!(window, all[offset..offset + window.len()]);
+        assert_eq!(
+            selected,
+            fresh.table_state.selected().map(|i| fresh.items[i].id)
+        );
+    }
+
+    #[test]
+    fn edits_in_place() {
+        let conn = db::open(std::path::Path::new(":memory:")).unwrap();
+        let mut app = App::new();
+        let tea = db::insert_item(&conn, &item(&app, "2026-10-01", "Drinks", "Tea", 250)).unwrap();
+        let mut egg = item(&app, "2026-10-02", "Food", "Egg", 199);
+        egg.currency = String::from("USD");
+        db::insert_item(&conn, &egg).unwrap();
+        reload(&mut app, &conn).unwrap();
+        assert_eq!((app.total, app.unconverted), (250, 1));
+
+        // New values are offered, and those no longer used are not
+        edit(&mut app, &conn, tea, |item| {
+            item.category = String::from("Food");
+            item.description = String::from("Green tea");
+            item.store = String::from("Aldi");
+            item.tags = vec![String::from("organic")];
+            item.price = 320;
+        });
+        assert_eq!(app.total, 320);
+        assert_eq!(app.distinct_categories, ["Food"]);
+        assert_eq!(app.distinct_descriptions, ["Egg", "Green tea"]);
+        assert_fresh(&app, &conn);
+
+        let jam = db::insert_item(&conn, &item(&app, "2026-09-30", "Food", "Jam", 400)).unwrap();
+        apply(&mut app, &conn, vec![Before::new_item(jam)]).unwrap();
+        assert_eq!(app.total, 720);
+        assert_eq!(app.items[0].id, jam);
+        assert_fresh(&app, &conn);
+
+        let changes = before(&app, &conn, &[jam]).unwrap();
+        db::delete_item(&conn, jam).unwrap();
+        apply(&mut app, &conn, changes).unwrap();
+        assert_eq!(app.total, 320);
+        assert_eq!(app.distinct_descriptions, ["Egg", "Green tea"]);
+        assert_fresh(&app, &conn);
+
+        // Out of the filter and back
+        app.filter.query = Some(crate::filter::parse(&app, "price>3").unwrap());
+        reload(&mut app, &conn).unwrap();
+        edit(&mut app, &conn, tea, |item| item.price = 300);
+        assert_eq!((app.item_count, app.total, app.unconverted), (0, 0, 0));
+        assert_fresh(&app, &conn);
+        edit(&mut app, &conn, tea, |item| item.price = 301);
+        assert_eq!((app.item_count, app.total), (1, 301));
+        assert_fresh(&app, &conn);
+    }
+
+    #[test]
+    fn edits_outside_window() {
+        let conn = db::open(std::path::Path::new(":memory:")).unwrap();
+        let mut app = App::new();
+        let mut ids = Vec::new();
+        for i in 0..600 {
+            let date = format!(
+                "{}-{:02}-{:02}",
+                2020 + i / 336,
+                1 + i / 28 % 12,
+                1 + i % 28
+            );
+            let description = format!("Item {}", i);
+            ids.push(
+                db::insert_item(&conn, &item(&app, &date, "Food", &description, 100)).unwrap(),
+            );
+        }
+        reload(&mut app, &conn).unwrap();
+        window::select(&mut app, &conn, Some(ids[300])).unwrap();
+        assert!(app.items_offset > 0);
+        assert_fresh(&app, &conn);
+
+        // Moved from after the window to before it, and back
+        let last = ids[599];
+        edit(&mut app, &conn, last, |item| {
+            item.date = String::from("2019-01-01")
+        });
+        assert_fresh(&app, &conn);
+        edit(&mut app, &conn, last, |item| {
+            item.date = String::from("2029-12-31")
+        });
+        assert_fresh(&app, &conn);
+
+        // Within the window, past the cursor
+        let first = app.items[0].id;
+        edit(&mut app, &conn, first, |item| item.price = 5000);
+        let cursor = app.table_state.selected().unwrap();
+        let date = app.items[cursor + 1].date.clone();
+        edit(&mut app, &conn, first, |item| item.date = date);
+        assert_eq!(app.total, 600 * 100 + 4900);
+        assert_fresh(&app, &conn);
+    }
+}
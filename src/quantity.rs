@@ -0,0 +1,87 @@
+use crate::locale::Locale;
+
+// Units that an item may be measured in.  Each unit is normalised to a
+// base unit (pcs, kg, l) so that unit prices can be compared.
+const UNITS: &[(&str, &str, f64)] = &[
+    // (unit, base unit, factor)
+    ("pcs", "pcs", 1.0),
+    ("g", "kg", 0.001),
+    ("kg", "kg", 1.0),
+    ("ml", "l", 0.001),
+    ("l", "l", 1.0),
+];
+
+fn lookup(unit: &str) -> Option<(&'static str, &'static str, f64)> {
+    UNITS.iter().find(|&&(u, _, _)| u == unit).copied()
+}
+
+// Quantity with an optional unit, e.g. "3", "2 kg", "500g" or "0,5 l" where
+// "," is the decimal separator.  A bare number counts pieces.
+pub fn parse_quantity(locale: &Locale, line: &str) -> Option<(f64, String)> {
+    let line = line.trim();
+    let split = line
+        .find(|c: char| !locale.is_number_char(c))
+        .unwrap_or(line.len());
+    let (number, unit) = line.split_at(split);
+
+    // A space that groups thousands may also separate the unit, as in "2 kg"
+    let quantity = locale
+        .parse_decimal(number.trim_end())
+        .filter(|q| *q > 0.0)?;
+    let unit = match unit.trim().to_ascii_lowercase().as_str() {
+        "" => "pcs",
+        unit => lookup(unit)?.0,
+    };
+
+    Some((quantity, String::from(unit)))
+}
+
+pub fn format_quantity(quantity: f64, unit: &str) -> String {
+    if unit.is_empty() {
+        String::new()
+    } else {
+        format!("{} {}", quantity, unit)
+    }
+}
+
+// Price per base unit, rounded to the nearest minor unit, e.g. the price of
+// 500 g is doubled to give the price per kg.
+pub fn unit_price(price: i64, quantity: f64, unit: &str) -> Option<(i64, &'static str)> {
+    let (_, base, factor) = lookup(unit)?;
+    let quantity = quantity * factor;
+
+    if quantity > 0.0 {
+        Some(((price as f64 / quantity).round() as i64, base))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(locale: &Locale, line: &str) -> Option<String> {
+        parse_quantity(locale, line).map(|(quantity, unit)| format_quantity(quantity, &unit))
+    }
+
+    #[test]
+    fn quantities() {
+        let plain = Locale::default();
+        assert_eq!(read(&plain, "3").as_deref(), Some("3 pcs"));
+        assert_eq!(read(&plain, "500g").as_deref(), Some("500 g"));
+        assert_eq!(read(&plain, "0.375 KG").as_deref(), Some("0.375 kg"));
+        assert_eq!(read(&plain, "0"), None);
+        assert_eq!(read(&plain, "2 cups"), None);
+
+        let french = Locale {
+            decimal: ',',
+            grouping: Some(' '),
+            ..Locale::default()
+        };
+        assert_eq!(read(&french, "0,5 l").as_deref(), Some("0.5 l"));
+        assert_eq!(read(&french, "1 500 g").as_deref(), Some("1500 g"));
+        assert_eq!(read(&french, "2 kg").as_deref(), Some("2 kg"));
+        assert_eq!(read(&french, "1.5kg").as_deref(), Some("1.5 kg"));
+    }
+}
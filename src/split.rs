@@ -0,0 +1,189 @@
+use crate::app::App;
+
+// Part of the price of a split item, allocated to a category, in the
+// item's currency.  A receipt line such as "Supermarket 87.40" may be
+// split into food, household and pet supplies, which then count towards
+// their own categories rather than the item's.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Split {
+    pub category: String,
+    pub price: i64,
+}
+
+// Allocations as typed, e.g. "Food=50; Household=30.40; Pet", where the
+// last category may leave out its amount to take what is left, which must
+// be more than nothing and of the same sign as the price.  Amounts
+// may be arithmetic, as for prices, and must add up to the price.  An
+// empty line removes the split.
+pub fn parse(app: &App, line: &str, price: i64, currency: &str) -> Result<Vec<Split>, String> {
+    if line.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let parts: Vec<&str> = line.split(';').map(str::trim).collect();
+    let mut splits = Vec::new();
+    let mut sum = 0i64;
+
+    for (i, part) in parts.iter().enumerate() {
+        let (category, amount) = match part.split_once('=') {
+            Some((category, amount)) => (category.trim(), Some(amount.trim())),
+            None => (*part, None),
+        };
+        if category.is_empty() {
+            return Err(String::from("missing category"));
+        }
+
+        let amount = match amount {
+            Some(amount) => crate::expr::evaluate(&app.locale, amount, app.exponent(currency))
+                .ok_or_else(|| format!("invalid amount {}", amount))?,
+            None if i == parts.len() - 1 => remainder(app, price, sum, category, currency)?,
+            None => return Err(format!("missing amount for {}", category)),
+        };
+
+        sum = sum
+            .checked_add(amount)
+            .ok_or_else(|| String::from("amounts too large"))?;
+        splits.push(Split {
+            category: String::from(category),
+            price: amount,
+        });
+    }
+
+    if splits.len() < 2 {
+        return Err(String::from("split into at least two categories"));
+    }
+    if sum < price {
+        return Err(format!(
+            "{} left over",
+            app.format_price(price - sum, currency)
+        ));
+    }
+    if sum > price {
+        return Err(format!(
+            "{} too much",
+            app.format_price(sum - price, currency)
+        ));
+    }
+
+    Ok(splits)
+}
+
+// What is left of the price for the last category, if anything.
+fn remainder(
+    app: &App,
+    price: i64,
+    sum: i64,
+    category: &str,
+    currency: &str,
+) -> Result<i64, String> {
+    let rest = price
+        .checked_sub(sum)
+        .ok_or_else(|| String::from("amounts too large"))?;
+
+    if rest == 0 {
+        Err(format!("nothing left for {}", category))
+    } else if rest.signum() != price.signum() {
+        Err(format!(
+            "{} too much",
+            app.format_price(rest.abs(), currency)
+        ))
+    } else {
+        Ok(rest)
+    }
+}
+
+// Allocations in the form parse reads.
+pub fn to_line(app: &App, splits: &[Split], currency: &str) -> String {
+    splits
+        .iter()
+        .map(|split| {
+            format!(
+                "{}={}",
+                split.category,
+                app.format_price(split.price, currency)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(category: &str, price: i64) -> Split {
+        Split {
+            category: String::from(category),
+            price,
+        }
+    }
+
+    #[test]
+    fn parse_and_print() {
+        let app = App::new();
+        let splits = parse(&app, "Food=50; Household=30.40; Pet", 8740, "").unwrap();
+        assert_eq!(
+            splits,
+            vec![
+                split("Food", 5000),
+                split("Household", 3040),
+                split("Pet", 700),
+            ]
+        );
+        let line = to_line(&app, &splits, "");
+        assert_eq!(line, "Food=50.00; Household=30.40; Pet=7.00");
+        assert_eq!(parse(&app, &line, 8740, ""), Ok(splits));
+
+        let refund = parse(&app, "Food=-2*1.5; Pet", -500, "").unwrap();
+        assert_eq!(refund, vec![split("Food", -300), split("Pet", -200)]);
+        assert_eq!(
+            parse(&app, &to_line(&app, &refund, ""), -500, ""),
+            Ok(refund)
+        );
+
+        assert_eq!(parse(&app, " ", 8740, ""), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn errors() {
+        let app = App::new();
+        let supermarket = |line| parse(&app, line, 8740, "");
+
+        assert_eq!(
+            supermarket("Food=87.40; Pet"),
+            Err(String::from("nothing left for Pet"))
+        );
+        assert_eq!(
+            supermarket("Food=90; Pet"),
+            Err(String::from("2.60 too much"))
+        );
+        assert_eq!(
+            parse(&app, "Food=-6; Pet", -500, ""),
+            Err(String::from("1.00 too much"))
+        );
+        assert_eq!(
+            supermarket("Food=80; Pet=7"),
+            Err(String::from("0.40 left over"))
+        );
+        assert_eq!(
+            supermarket("Food=80; Pet=8"),
+            Err(String::from("0.60 too much"))
+        );
+        assert_eq!(
+            supermarket("Food"),
+            Err(String::from("split into at least two categories"))
+        );
+        assert_eq!(
+            supermarket("Food; Pet"),
+            Err(String::from("missing amount for Food"))
+        );
+        assert_eq!(
+            supermarket("=5; Pet"),
+            Err(String::from("missing category"))
+        );
+        assert_eq!(
+            supermarket("Food=abc; Pet"),
+            Err(String::from("invalid amount abc"))
+        );
+    }
+}
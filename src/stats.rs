@@ -0,0 +1,270 @@
+use crate::app::{App, DbItem, ItemKind};
+use crate::{quantity, tax};
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Clone, Debug)]
+pub struct StoreStats {
+    pub store: String,
+
+    // Number of distinct days with a purchase at the store.
+    pub visits: usize,
+
+    // Amount spent, in the home currency.
+    pub total: i64,
+
+    // Number of items that could not be converted to the home currency.
+    pub unconverted: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct Cheapest {
+    pub description: String,
+    pub store: String,
+
+    // Lowest price paid, per base unit if the quantity was recorded.
+    pub price: i64,
+    pub unit: &'static str,
+}
+
+#[derive(Clone, Debug)]
+pub struct TaxRow {
+    // Month, e.g. "2026-10", or year, or empty for the total of all.
+    pub period: String,
+
+    // Name of the tax rate and the rate, or empty for items without tax.
+    pub name: String,
+    pub rate: i64,
+
+    // Amount including tax, and the tax, in the home currency.
+    pub gross: i64,
+    pub tax: i64,
+
+    // Number of items that could not be converted to the home currency.
+    pub unconverted: usize,
+}
+
+// The statistics are worked out from items as they are read, see
+// db::scan_items, so that they need not all be held in memory.
+pub fn store_stats(app: &App, items: impl Iterator<Item = DbItem>) -> Vec<StoreStats> {
+    let mut map: BTreeMap<String, (BTreeSet<String>, StoreStats)> = BTreeMap::new();
+
+    for item in items.filter(|item| !item.store.is_empty()) {
+        let price = app.home_price(&item);
+        let (days, stats) = map.entry(item.store.clone()).or_insert_with(|| {
+            let stats = StoreStats {
+                store: item.store.clone(),
+                visits: 0,
+                total: 0,
+                unconverted: 0,
+            };
+            (BTreeSet::new(), stats)
+        });
+
+        days.insert(item.date);
+        match price {
+            Some(price) => stats.total += price,
+            None => stats.unconverted += 1,
+        }
+    }
+
+    map.into_values()
+        .map(|(days, mut stats)| {
+            stats.visits = days.len();
+            stats
+        })
+        .collect()
+}
+
+// For each description, the store where it was bought for the lowest price.
+// Prices are compared in the home currency, per base unit where possible,
+// so 500 g and 1 kg compare per kg.  Quantities that have no common base,
+// e.g. pieces and kg, or none at all, are compared separately.
+pub fn cheapest(app: &App, items: impl Iterator<Item = DbItem>) -> Vec<Cheapest> {
+    let mut map: BTreeMap<(String, &str), Cheapest> = BTreeMap::new();
+
+    // Refunds and discounts are not prices anything can be bought at
+    let purchases = items.filter(|item| item.kind == ItemKind::Purchase && !item.store.is_empty());
+
+    for item in purchases {
+        let price = match app.home_price(&item) {
+            Some(price) => price,
+            None => continue,
+        };
+        let (price, unit) =
+            quantity::unit_price(price, item.quantity, &item.unit).unwrap_or((price, ""));
+
+        let candidate = Cheapest {
+            description: item.description.clone(),
+            store: item.store,
+            price,
+            unit,
+        };
+
+        map.entry((item.description, unit))
+            .and_modify(|best| {
+                if candidate.price < best.price {
+                    *best = candidate.clone();
+                }
+            })
+            .or_insert(candidate);
+    }
+
+    map.into_values().collect()
+}
+
+#[derive(Clone, Debug)]
+pub struct CategoryNode {
+    // Full category name, e.g. "Food:Dairy".
+    pub path: String,
+
+    // Last level of the name, e.g. "Dairy", and how deep it is.
+    pub name: String,
+    pub depth: usize,
+
+    // Items in exactly this category, and their amount.
+    pub count: usize,
+    pub total: i64,
+
+    // Amount including all subcategories.
+    pub subtotal: i64,
+
+    // Number of items that could not be converted to the home currency,
+    // in exactly this category and including all subcategories.
+    pub unconverted: usize,
+    pub subunconverted: usize,
+}
+
+// Categories as a tree split on `separator`, in depth-first order, with
+// amounts in the home currency rolled up to each parent.  Parents that
+// are never used on their own still get a node.  Split items count once
+// for each of their allocations, rather than for their own category.
+pub fn category_tree(
+    app: &App,
+    items: impl Iterator<Item = DbItem>,
+    separator: &str,
+) -> Vec<CategoryNode> {
+    let mut map: BTreeMap<Vec<String>, CategoryNode> = BTreeMap::new();
+
+    for item in items {
+        for (category, amount) in item.allocations() {
+            let price = app.home_amount(amount, &item.currency, &item.date);
+            let levels: Vec<&str> = category.split(separator).collect();
+
+            for depth in 0..levels.len() {
+                let key = levels[..=depth].iter().map(|&l| String::from(l)).collect();
+                let node = map.entry(key).or_insert_with(|| CategoryNode {
+                    path: levels[..=depth].join(separator),
+                    name: String::from(levels[depth]),
+                    depth,
+                    count: 0,
+                    total: 0,
+                    subtotal: 0,
+                    unconverted: 0,
+                    subunconverted: 0,
+                });
+
+                let is_own = depth == levels.len() - 1;
+                if is_own {
+                    node.count += 1;
+                }
+                match price {
+                    Some(price) if is_own => {
+                        node.total += price;
+                        node.subtotal += price;
+                    }
+                    Some(price) => node.subtotal += price,
+                    None if is_own => {
+                        node.unconverted += 1;
+                        node.subunconverted += 1;
+                    }
+                    None => node.subunconverted += 1,
+                }
+            }
+        }
+    }
+
+    map.into_values().collect()
+}
+
+// Tax paid per rate in each month, or year, in order, followed by the
+// total per rate.
+pub fn tax_report(app: &App, items: impl Iterator<Item = DbItem>, yearly: bool) -> Vec<TaxRow> {
+    let mut map: BTreeMap<(bool, String, String), TaxRow> = BTreeMap::new();
+    let len = if yearly { 4 } else { 7 };
+
+    // Each allocation of a split item at the rate of its own category
+    for item in items {
+        let period = item.date.get(..len).unwrap_or(&item.date);
+
+        for (category, amount) in item.allocations() {
+            let (name, rate) = app
+                .tax_rates
+                .of_allocation(&item, category, &app.category_separator)
+                .unwrap_or(("", 0));
+            let price = app.home_amount(amount, &item.currency, &item.date);
+
+            for (is_total, period) in [(false, period), (true, "")] {
+                let row = map
+                    .entry((is_total, String::from(period), String::from(name)))
+                    .or_insert_with(|| TaxRow {
+                        period: String::from(period),
+                        name: String::from(name),
+                        rate,
+                        gross: 0,
+                        tax: 0,
+                        unconverted: 0,
+                    });
+
+                match price {
+                    Some(price) => {
+                        row.gross += price;
+                        row.tax += tax::included(price, rate);
+                    }
+                    None => row.unconverted += 1,
+                }
+            }
+        }
+    }
+
+    map.into_values().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(app: &App, store: &str, price: i64, quantity: f64, unit: &str) -> DbItem {
+        let mut item = app.new_item.clone();
+        item.date = String::from("2026-10-01");
+        item.description = String::from("Cheese");
+        item.store = String::from(store);
+        item.price = price;
+        item.quantity = quantity;
+        item.unit = String::from(unit);
+        item
+    }
+
+    #[test]
+    fn cheapest_per_base_unit() {
+        let app = App::new();
+        let items = [
+            item(&app, "Aldi", 299, 100.0, "g"),
+            item(&app, "Lidl", 1990, 1.0, "kg"),
+            item(&app, "Rewe", 450, 250.0, "g"),
+            item(&app, "Rewe", 350, 1.0, "pcs"),
+            item(&app, "Aldi", 399, 1.0, "pcs"),
+        ];
+
+        let best: Vec<(String, i64, &str)> = cheapest(&app, items.into_iter())
+            .into_iter()
+            .map(|best| (best.store, best.price, best.unit))
+            .collect();
+        assert_eq!(
+            best,
+            [
+                (String::from("Rewe"), 1800, "kg"),
+                (String::from("Rewe"), 350, "pcs")
+            ]
+        );
+    }
+}
@@ -0,0 +1,183 @@
+use crate::app::DbItem;
+use crate::db;
+use rusqlite::{Connection, Result};
+use std::collections::BTreeMap;
+
+// Tax rates by name, e.g. "standard" at 19%, in hundredths of a percent,
+// and the rate that applies by default to items of each category.  A
+// default also applies to subcategories without one of their own.  Prices
+// include tax, which is worked out from them.  Rates are settings and not
+// recorded in the journal, so undo may bring back an item's rate that has
+// since been deleted, which is then ignored.
+#[derive(Clone, Debug, Default)]
+pub struct TaxRates {
+    pub rates: BTreeMap<String, i64>,
+    pub categories: BTreeMap<String, String>,
+}
+
+impl TaxRates {
+    pub fn load(conn: &Connection) -> Result<TaxRates> {
+        Ok(TaxRates {
+            rates: db::select_tax_rates(conn)?,
+            categories: db::select_category_taxes(conn)?,
+        })
+    }
+
+    // Name and rate of the tax on an item, if any.
+    pub fn of_item(&self, item: &DbItem, separator: &str) -> Option<(&str, i64)> {
+        self.of_allocation(item, &item.category, separator)
+    }
+
+    // Name and rate of the tax on the part of a split item allocated to a
+    // category, which is that of the item if it has its own and it still
+    // exists.
+    pub fn of_allocation(
+        &self,
+        item: &DbItem,
+        category: &str,
+        separator: &str,
+    ) -> Option<(&str, i64)> {
+        item.tax_rate
+            .as_deref()
+            .and_then(|name| self.get(name))
+            .or_else(|| self.of_category(category, separator))
+    }
+
+    // Rate that applies to a category by default, from the category itself
+    // or the closest parent with one.
+    pub fn of_category(&self, category: &str, separator: &str) -> Option<(&str, i64)> {
+        let mut category = category;
+        loop {
+            if let Some(name) = self.categories.get(category) {
+                return self.get(name);
+            }
+            category = &category[..category.rfind(separator)?];
+        }
+    }
+
+    fn get(&self, name: &str) -> Option<(&str, i64)> {
+        self.rates
+            .get_key_value(name)
+            .map(|(name, &rate)| (name.as_str(), rate))
+    }
+}
+
+// Tax included in a gross amount, rounded to the nearest minor unit with
+// halves away from zero.
+pub fn included(gross: i64, rate: i64) -> i64 {
+    let num = i128::from(gross) * i128::from(rate);
+    let den = 10000 + i128::from(rate);
+    let tax = (num.abs() * 2 + den) / (den * 2);
+    (num.signum() * tax) as i64
+}
+
+// Name of a rate with the rate, e.g. "reduced 7%".
+pub fn describe(name: &str, rate: i64) -> String {
+    format!("{} {}", name, format_rate(rate))
+}
+
+// Rate as a percentage, e.g. "19%" or "5.5%".
+pub fn format_rate(rate: i64) -> String {
+    let percent = format!("{}.{:02}", rate / 100, rate % 100);
+    format!("{}%", percent.trim_end_matches('0').trim_end_matches('.'))
+}
+
+// Percentage with up to two decimals, e.g. "19" or "5.5", with an
+// optional "%", in hundredths of a percent.
+pub fn parse_rate(text: &str) -> Option<i64> {
+    let text = text.trim().trim_end_matches('%');
+    let (whole, fraction) = text.split_once('.').unwrap_or((text, ""));
+    if whole.is_empty() || fraction.len() > 2 {
+        return None;
+    }
+    if !(whole.chars().chain(fraction.chars())).all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let fraction = format!("{:0<2}", fraction);
+    let rate = whole.parse::<i64>().ok()?.checked_mul(100)? + fraction.parse::<i64>().ok()?;
+    (rate <= 100 * 100).then_some(rate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::App;
+
+    #[test]
+    fn included_tax() {
+        assert_eq!(included(119, 1900), 19);
+        assert_eq!(included(1000, 1900), 160);
+        assert_eq!(included(-1000, 1900), -160);
+        assert_eq!(included(107, 700), 7);
+        assert_eq!(included(0, 1900), 0);
+        assert_eq!(included(1000, 0), 0);
+        // 10.50 at 5% includes 0.50, and 0.21 at 5% includes exactly half a cent
+        assert_eq!(included(1050, 500), 50);
+        assert_eq!(included(21, 500), 1);
+        assert_eq!(included(-21, 500), -1);
+    }
+
+    #[test]
+    fn rates() {
+        assert_eq!(parse_rate("19"), Some(1900));
+        assert_eq!(parse_rate("19%"), Some(1900));
+        assert_eq!(parse_rate(" 5.5% "), Some(550));
+        assert_eq!(parse_rate("0.07"), Some(7));
+        assert_eq!(parse_rate("100"), Some(10000));
+        assert_eq!(parse_rate("100.01"), None);
+        assert_eq!(parse_rate("5.555"), None);
+        assert_eq!(parse_rate(".5"), None);
+        assert_eq!(parse_rate("-5"), None);
+        assert_eq!(parse_rate("5,5"), None);
+        assert_eq!(parse_rate("92233720368547758"), None);
+
+        for rate in [0, 7, 550, 1900, 10000] {
+            assert_eq!(parse_rate(&format_rate(rate)), Some(rate));
+        }
+        assert_eq!(format_rate(1900), "19%");
+        assert_eq!(format_rate(550), "5.5%");
+        assert_eq!(format_rate(7), "0.07%");
+        assert_eq!(describe("reduced", 700), "reduced 7%");
+    }
+
+    #[test]
+    fn nested_categories() {
+        let taxes = TaxRates {
+            rates: BTreeMap::from([
+                (String::from("standard"), 1900),
+                (String::from("reduced"), 700),
+            ]),
+            categories: BTreeMap::from([
+                (String::from("Food"), String::from("reduced")),
+                (String::from("Food:Drinks"), String::from("standard")),
+                (String::from("Pet"), String::from("deleted")),
+            ]),
+        };
+
+        assert_eq!(taxes.of_category("Food", ":"), Some(("reduced", 700)));
+        assert_eq!(taxes.of_category("Food:Bread", ":"), Some(("reduced", 700)));
+        assert_eq!(
+            taxes.of_category("Food:Drinks:Juice", ":"),
+            Some(("standard", 1900))
+        );
+        assert_eq!(taxes.of_category("Foods", ":"), None);
+        assert_eq!(taxes.of_category("Household", ":"), None);
+        assert_eq!(
+            taxes.of_category("Food/Drinks", "/"),
+            Some(("reduced", 700))
+        );
+
+        let mut item = App::new().new_item;
+        item.category = String::from("Food:Drinks");
+        assert_eq!(taxes.of_item(&item, ":"), Some(("standard", 1900)));
+        item.tax_rate = Some(String::from("reduced"));
+        assert_eq!(taxes.of_item(&item, ":"), Some(("reduced", 700)));
+        item.tax_rate = Some(String::from("deleted"));
+        assert_eq!(taxes.of_item(&item, ":"), Some(("standard", 1900)));
+        assert_eq!(
+            taxes.of_allocation(&item, "Food:Bread", ":"),
+            Some(("reduced", 700))
+        );
+    }
+}
@@ -1,17 +1,23 @@
-use crate::app::{App, AppState, DbItem};
+use crate::app::{App, AppState, DbItem, ItemKind, SortColumn};
+use crate::filter;
+use crate::household;
+use crate::quantity;
+use crate::split;
+use crate::tax;
 use crate::util;
+use crate::worker::Busy;
 use ratatui::{prelude::*, widgets::*};
 
 const KEY_BAR_ITEMS: &[(&str, &str)] = &[
     (" 1", "Help"),
     (" 2", "Sort"),
-    (" 3", "    "),
+    (" 3", "Order"),
     (" 4", "Edit"),
-    (" 5", "    "),
-    (" 6", "    "),
+    (" 5", "Stores"),
+    (" 6", "Receipt"),
     (" 7", "Insert"),
     (" 8", "Delete"),
-    (" 9", "    "),
+    (" 9", "Tags"),
     ("10", "Quit"),
 ];
 
@@ -25,79 +31,856 @@ pub fn render_tui(frame: &mut Frame, app: &mut App) {
         ])
         .split(frame.area());
 
-    render_table(frame, layout[0], app);
+    match app.state {
+        AppState::Stores => render_stores(frame, layout[0], app),
+        AppState::Categories
+        | AppState::RenameCategory
+        | AppState::MergeCategories
+        | AppState::CategoryTax => render_categories(frame, layout[0], app),
+        AppState::TaxReport => render_tax_report(frame, layout[0], app),
+        AppState::Balances | AppState::SettleUp => render_balances(frame, layout[0], app),
+        AppState::Trash | AppState::EmptyTrash | AppState::PurgeItem => {
+            render_trash(frame, layout[0], app)
+        }
+        _ => render_table(frame, layout[0], app),
+    }
     render_text_area(frame, layout[1], app);
     render_key_bar(frame, layout[2]);
     render_text_completion(frame, app);
 }
 
 fn render_table(frame: &mut Frame, layout: Rect, app: &mut App) {
-    let header = Row::new(vec![
-        Cell::from(Line::from("Id").alignment(Alignment::Center)),
-        Cell::from(Line::from("Date").alignment(Alignment::Center)),
-        Cell::from(Line::from("Category")),
-        Cell::from(Line::from("Description")),
-        Cell::from(Line::from("Price").alignment(Alignment::Center)),
-    ])
-    .style(Style::default().fg(Color::LightYellow));
-
-    let mut body: Vec<Row> = Vec::new();
+    let arrow = if app.sort.descending { "▼" } else { "▲" };
+    let title = |column: SortColumn, text: &str| {
+        if app.sort.column == column {
+            format!("{}{}", text, arrow)
+        } else {
+            String::from(text)
+        }
+    };
 
-    for item in &app.items {
-        body.push(make_table_row(item));
-    }
+    let mut header = vec![
+        Cell::from(Line::from(title(SortColumn::Id, "Id")).alignment(Alignment::Center)),
+        Cell::from(Line::from(title(SortColumn::Date, "Date")).alignment(Alignment::Center)),
+        Cell::from(Line::from(title(SortColumn::Category, "Category"))),
+        Cell::from(Line::from(title(SortColumn::Description, "Description"))),
+        Cell::from(Line::from("Store")),
+        Cell::from(Line::from("Tags")),
+        Cell::from(Line::from("Qty").alignment(Alignment::Center)),
+        Cell::from(Line::from(title(SortColumn::Price, "Price")).alignment(Alignment::Center)),
+        Cell::from(Line::from("Unit price").alignment(Alignment::Center)),
+    ];
 
     let mut widths = vec![
         Constraint::Length(6),                 // id
         Constraint::Length(4 + 1 + 2 + 1 + 2), // date
         Constraint::Length(0),                 // category
         Constraint::Min(0),                    // description
-        Constraint::Length(5 + 1 + 2),         // price
+        Constraint::Length(0),                 // store
+        Constraint::Length(0),                 // tags
+        Constraint::Length(4 + 1 + 3),         // quantity, unit
+        Constraint::Length(amount_width(
+            5 + 1 + 2 + 1 + 3,
+            app.items
+                .iter()
+                .map(|item| app.format_money(item.price, &item.currency)),
+        )), // price, currency
+        Constraint::Length(amount_width(
+            5 + 1 + 2 + 1 + 3,
+            app.items.iter().map(|item| format_unit_price(app, item)),
+        )), // unit price, base unit
     ];
 
+    // Converted amount, only when there is a home currency to convert to
+    let show_home = !app.home_currency.is_empty();
+    if show_home {
+        header.push(Cell::from(
+            Line::from(app.home_currency.clone()).alignment(Alignment::Center),
+        ));
+        widths.push(Constraint::Length(amount_width(
+            5 + 1 + 2,
+            app.items
+                .iter()
+                .filter_map(|item| app.home_price(item))
+                .map(|price| app.format_price(price, "")),
+        )));
+    }
+
+    let header = Row::new(header).style(Style::default().fg(Color::LightYellow));
+
+    // Scroll so that the cursor is visible, and build rows only for the
+    // items that fit between the borders and header
+    let height = usize::from(layout.height.saturating_sub(3));
+    let cursor = app.table_state.selected();
+    if let Some(cursor) = cursor {
+        if cursor < app.table_scroll {
+            app.table_scroll = cursor;
+        } else if cursor >= app.table_scroll + height {
+            app.table_scroll = cursor + 1 - height;
+        }
+    }
+    app.table_scroll = app.table_scroll.min(app.items.len().saturating_sub(height));
+
+    // Allocations of expanded items take rows of their own, so scroll on
+    // until those of the cursor's item fit too
+    let rows = |item: &DbItem| {
+        1 + if app.expanded.contains(&item.id) {
+            item.splits.len()
+        } else {
+            0
+        }
+    };
+    if let Some(cursor) = cursor {
+        while app.table_scroll < cursor
+            && app.items[app.table_scroll..=cursor]
+                .iter()
+                .map(rows)
+                .sum::<usize>()
+                > height
+        {
+            app.table_scroll += 1;
+        }
+    }
+
+    let mut body: Vec<Row> = Vec::new();
+    let mut selected_row = None;
+
+    let terms = util::search_terms(&app.filter.search);
+
+    for (i, item) in app.items.iter().enumerate().skip(app.table_scroll) {
+        if body.len() >= height {
+            break;
+        }
+        if cursor == Some(i) {
+            selected_row = Some(body.len());
+        }
+
+        let home_price = app.home_price(item);
+        let mut row = make_table_row(app, item, &terms);
+        if show_home {
+            let price = home_price.map_or(String::from("?"), |price| app.format_price(price, ""));
+            row.push(Cell::from(Line::from(price).alignment(Alignment::Right)));
+        }
+        body.push(Row::new(row));
+
+        if app.expanded.contains(&item.id) {
+            for split in &item.splits {
+                let mut row = make_split_row(app, item, split);
+                if show_home {
+                    let price = app
+                        .home_amount(split.price, &item.currency, &item.date)
+                        .map_or(String::from("?"), |price| app.format_price(price, ""));
+                    row.push(Cell::from(Line::from(price).alignment(Alignment::Right)));
+                }
+                body.push(Row::new(row).style(Style::default().fg(Color::Gray)));
+            }
+        }
+    }
+
+    let mut summary = format!(
+        " Total: {} ",
+        app.format_money(app.total, &app.home_currency)
+    );
+    if app.unconverted > 0 {
+        summary += &format!("(+{} without exchange rate) ", app.unconverted);
+    }
+
+    let position = match cursor {
+        Some(cursor) => format!(" {}/{} ", app.items_offset + cursor + 1, app.item_count),
+        None => String::from(" 0/0 "),
+    };
+
+    let mut block = Block::default()
+        .borders(Borders::ALL)
+        .title(Line::from(position).right_aligned())
+        .title_bottom(Line::from(summary).right_aligned());
+
+    if !app.filter.tags.is_empty() {
+        let tags = format!(
+            " Tags: {} of {} ",
+            if app.filter.tags.all { "all" } else { "any" },
+            app.filter.tags.tags.join(", ")
+        );
+        block = block.title(Line::from(tags).left_aligned());
+    }
+
+    if !app.filter.search.is_empty() {
+        let search = format!(" Search: {} ", app.filter.search);
+        block = block.title(Line::from(search).left_aligned());
+    }
+
+    if let Some(query) = &app.filter.query {
+        let filter = format!(" Filter: {} ", query.text);
+        block = block.title(Line::from(filter).left_aligned());
+    }
+
+    // The receipt has no id until its first item is saved
+    let entering = matches!(
+        app.state,
+        AppState::ReceiptDate | AppState::ReceiptStore | AppState::ReceiptTotal
+    );
+    if let Some(receipt) = app.receipt.as_ref().filter(|_| !entering) {
+        let id = match receipt.id {
+            0 => String::new(),
+            id => format!(" {}", id),
+        };
+        let running = format!(
+            " Receipt{}: {} of {} ",
+            id,
+            app.format_money(app.receipt_sum, &receipt.currency),
+            app.format_money(receipt.total, &receipt.currency)
+        );
+        block = block.title_bottom(Line::from(running).left_aligned());
+    }
+
     let div = Layout::default()
         .direction(Direction::Horizontal)
         .constraints(widths.clone())
         .split(layout);
 
-    // Divide width between category and description
-    widths[2] = Constraint::Max(div[3].width / 3);
-    widths[3] = Constraint::Min(div[3].width * 2 / 3);
+    // Divide width between category, description, store and tags
+    widths[2] = Constraint::Max(div[3].width / 5);
+    widths[3] = Constraint::Min(div[3].width * 2 / 5);
+    widths[4] = Constraint::Max(div[3].width / 5);
+    widths[5] = Constraint::Max(div[3].width / 5);
 
     let table = Table::new(body, widths)
-        .block(Block::default().borders(Borders::ALL))
+        .block(block)
         .header(header)
         .style(Style::default().fg(Color::White).bg(Color::Blue))
         .row_highlight_style(Style::default().fg(Color::Black).bg(Color::Cyan));
 
-    frame.render_stateful_widget(table, layout, &mut app.table_state);
+    let mut state = TableState::default().with_selected(selected_row);
+    frame.render_stateful_widget(table, layout, &mut state);
 }
 
-fn make_table_row<'a>(item: &DbItem) -> Row<'a> {
+fn make_table_row<'a>(app: &App, item: &DbItem, terms: &[String]) -> Vec<Cell<'a>> {
     let id = format!("{}", item.id);
-    let price = util::format_price(item.price);
+    let price = app.format_money(item.price, &item.currency);
+    let unit_price = format_unit_price(app, item);
 
-    Row::new(vec![
+    vec![
         Cell::from(Line::from(id).alignment(Alignment::Right)),
         Cell::from(item.date.clone()),
-        Cell::from(item.category.clone()),
-        Cell::from(item.description.clone()),
+        Cell::from(highlight(&item.category, terms)),
+        Cell::from(describe(item, app.expanded.contains(&item.id), terms)),
+        Cell::from(item.store.clone()),
+        Cell::from(item.tags.join(" ")),
+        Cell::from(Line::from(quantity::format_quantity(
+            item.quantity,
+            &item.unit,
+        ))),
         Cell::from(Line::from(price).alignment(Alignment::Right)),
+        Cell::from(Line::from(unit_price).alignment(Alignment::Right)),
+    ]
+}
+
+// Row below a split item for one of its allocations.
+fn make_split_row<'a>(app: &App, item: &DbItem, split: &split::Split) -> Vec<Cell<'a>> {
+    let price = app.format_money(split.price, &item.currency);
+
+    vec![
+        Cell::from(""),
+        Cell::from(""),
+        Cell::from(format!("└ {}", split.category)),
+        Cell::from(""),
+        Cell::from(""),
+        Cell::from(""),
+        Cell::from(""),
+        Cell::from(Line::from(price).alignment(Alignment::Right)),
+        Cell::from(""),
+    ]
+}
+
+fn format_unit_price(app: &App, item: &DbItem) -> String {
+    quantity::unit_price(item.price, item.quantity, &item.unit)
+        .map(|(price, base)| format!("{}/{}", app.format_price(price, &item.currency), base))
+        .unwrap_or_default()
+}
+
+// Width of a column of amounts, which depends on the digits after the
+// decimal point of their currencies: the widest of them, or the default.
+fn amount_width(default: u16, amounts: impl Iterator<Item = String>) -> u16 {
+    amounts
+        .map(|amount| amount.chars().count() as u16)
+        .fold(default, u16::max)
+}
+
+// Description, after the kind of item and the purchase it applies to,
+// unless it is an ordinary purchase, and a marker on split items that
+// shows whether their allocations are expanded.
+fn describe<'a>(item: &DbItem, expanded: bool, terms: &[String]) -> Line<'a> {
+    let mut line = highlight(&item.description, terms);
+    if !item.splits.is_empty() {
+        let marker = if expanded { "▾ " } else { "▸ " };
+        line.spans.insert(
+            0,
+            Span::styled(marker, Style::default().fg(Color::LightGreen)),
+        );
+    }
+    if item.kind != ItemKind::Purchase {
+        let mut kind = format!("[{}", item.kind.sql());
+        if let Some(id) = item.original_id {
+            kind += &format!(" #{}", id);
+        }
+        kind += "] ";
+        line.spans.insert(
+            0,
+            Span::styled(kind, Style::default().fg(Color::LightGreen)),
+        );
+    }
+    line
+}
+
+// Text with search terms highlighted where they start a word.
+fn highlight<'a>(text: &str, terms: &[String]) -> Line<'a> {
+    let style = Style::default().fg(Color::Black).bg(Color::LightYellow);
+    let mut spans = Vec::new();
+    let mut start = 0;
+    let mut i = 0;
+
+    while i < text.len() {
+        let at_word = text[..i]
+            .chars()
+            .next_back()
+            .is_none_or(|c| !c.is_alphanumeric());
+        let len = terms
+            .iter()
+            .filter(|term| at_word && util::starts_with_ignore_case(&text[i..], term))
+            .map(|term| term.len())
+            .max();
+
+        match len {
+            Some(len) => {
+                if start < i {
+                    spans.push(Span::from(String::from(&text[start..i])));
+                }
+                spans.push(Span::styled(String::from(&text[i..i + len]), style));
+                i += len;
+                start = i;
+            }
+            None => i += text[i..].chars().next().map_or(1, char::len_utf8),
+        }
+    }
+
+    if start < text.len() {
+        spans.push(Span::from(String::from(&text[start..])));
+    }
+
+    Line::from(spans)
+}
+
+fn render_stores(frame: &mut Frame, layout: Rect, app: &mut App) {
+    let div = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(vec![Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(layout);
+
+    let header = Row::new(vec![
+        Cell::from(Line::from("Store")),
+        Cell::from(Line::from("Visits").alignment(Alignment::Center)),
+        Cell::from(Line::from("Total").alignment(Alignment::Center)),
+    ])
+    .style(Style::default().fg(Color::LightYellow));
+
+    let body: Vec<Row> = app
+        .store_stats
+        .iter()
+        .map(|stats| {
+            let mut total = app.format_price(stats.total, "");
+            if stats.unconverted > 0 {
+                total = format!("{} (+{}?)", total, stats.unconverted);
+            }
+            Row::new(vec![
+                Cell::from(stats.store.clone()),
+                Cell::from(Line::from(stats.visits.to_string()).alignment(Alignment::Right)),
+                Cell::from(Line::from(total).alignment(Alignment::Right)),
+            ])
+        })
+        .collect();
+
+    let widths = vec![
+        Constraint::Min(0),
+        Constraint::Length(6),
+        Constraint::Length(7 + 1 + 2 + 5),
+    ];
+
+    let table = Table::new(body, widths)
+        .block(Block::default().borders(Borders::ALL).title(" Stores "))
+        .header(header)
+        .style(Style::default().fg(Color::White).bg(Color::Blue))
+        .row_highlight_style(Style::default().fg(Color::Black).bg(Color::Cyan));
+
+    frame.render_stateful_widget(table, div[0], &mut app.view_state);
+
+    let header = Row::new(vec![
+        Cell::from(Line::from("Description")),
+        Cell::from(Line::from("Cheapest at")),
+        Cell::from(Line::from("Price").alignment(Alignment::Center)),
+    ])
+    .style(Style::default().fg(Color::LightYellow));
+
+    let body: Vec<Row> = app
+        .cheapest
+        .iter()
+        .map(|best| {
+            let mut price = app.format_price(best.price, "");
+            if !best.unit.is_empty() {
+                price = format!("{}/{}", price, best.unit);
+            }
+            Row::new(vec![
+                Cell::from(best.description.clone()),
+                Cell::from(best.store.clone()),
+                Cell::from(Line::from(price).alignment(Alignment::Right)),
+            ])
+        })
+        .collect();
+
+    let widths = vec![
+        Constraint::Percentage(50),
+        Constraint::Percentage(50),
+        Constraint::Length(amount_width(
+            5 + 1 + 2 + 1 + 3,
+            app.cheapest
+                .iter()
+                .map(|best| format!("{}/{}", app.format_price(best.price, ""), best.unit)),
+        )),
+    ];
+
+    let table = Table::new(body, widths)
+        .block(Block::default().borders(Borders::ALL))
+        .header(header)
+        .style(Style::default().fg(Color::White).bg(Color::Blue));
+
+    frame.render_widget(table, div[1]);
+}
+
+fn render_categories(frame: &mut Frame, layout: Rect, app: &mut App) {
+    let header = Row::new(vec![
+        Cell::from(Line::from("Category")),
+        Cell::from(Line::from("Items").alignment(Alignment::Center)),
+        Cell::from(Line::from("Own").alignment(Alignment::Center)),
+        Cell::from(Line::from("Subtotal").alignment(Alignment::Center)),
+        Cell::from(Line::from("Tax")),
+    ])
+    .style(Style::default().fg(Color::LightYellow));
+
+    let body: Vec<Row> = app
+        .category_tree
+        .iter()
+        .map(|node| {
+            let name = if node.name.is_empty() && node.depth == 0 {
+                "(none)"
+            } else {
+                node.name.as_str()
+            };
+            let own = if node.count > 0 {
+                app.format_price(node.total, "")
+            } else {
+                String::new()
+            };
+            let own = match node.unconverted {
+                0 => own,
+                n => format!("{} (+{}?)", own, n),
+            };
+            let mut subtotal = app.format_price(node.subtotal, "");
+            if node.subunconverted > 0 {
+                subtotal = format!("{} (+{}?)", subtotal, node.subunconverted);
+            }
+            // Rates inherited from a parent category are dimmed
+            let tax = app
+                .tax_rates
+                .of_category(&node.path, &app.category_separator)
+                .map(|(name, rate)| tax::describe(name, rate))
+                .unwrap_or_default();
+            let tax_style = if app.tax_rates.categories.contains_key(&node.path) {
+                Style::default()
+            } else {
+                Style::default().fg(Color::Gray)
+            };
+            let mark = if app.marked_categories.contains(&node.path) {
+                "* "
+            } else {
+                "  "
+            };
+            Row::new(vec![
+                Cell::from(format!("{}{}{}", mark, "  ".repeat(node.depth), name)),
+                Cell::from(Line::from(node.count.to_string()).alignment(Alignment::Right)),
+                Cell::from(Line::from(own).alignment(Alignment::Right)),
+                Cell::from(Line::from(subtotal).alignment(Alignment::Right)),
+                Cell::from(Span::styled(tax, tax_style)),
+            ])
+        })
+        .collect();
+
+    let widths = vec![
+        Constraint::Min(0),
+        Constraint::Length(6),
+        Constraint::Length(7 + 1 + 2 + 5),
+        Constraint::Length(7 + 1 + 2 + 5),
+        Constraint::Percentage(20),
+    ];
+
+    let table = Table::new(body, widths)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(" Categories ")
+                .title_bottom(
+                    Line::from(" Space: mark  r: rename  m: merge marked into…  t: tax rate ")
+                        .right_aligned(),
+                ),
+        )
+        .header(header)
+        .style(Style::default().fg(Color::White).bg(Color::Blue))
+        .row_highlight_style(Style::default().fg(Color::Black).bg(Color::Cyan));
+
+    frame.render_stateful_widget(table, layout, &mut app.view_state);
+}
+
+fn render_tax_report(frame: &mut Frame, layout: Rect, app: &mut App) {
+    let header = Row::new(vec![
+        Cell::from(Line::from(if app.tax_yearly { "Year" } else { "Month" })),
+        Cell::from(Line::from("Tax rate")),
+        Cell::from(Line::from("Gross").alignment(Alignment::Center)),
+        Cell::from(Line::from("Net").alignment(Alignment::Center)),
+        Cell::from(Line::from("Tax").alignment(Alignment::Center)),
+    ])
+    .style(Style::default().fg(Color::LightYellow));
+
+    let amount = |price: i64| Cell::from(Line::from(app.format_price(price, "")).right_aligned());
+
+    let body: Vec<Row> = app
+        .tax_report
+        .iter()
+        .map(|row| {
+            let period = if row.period.is_empty() {
+                "Total"
+            } else {
+                row.period.as_str()
+            };
+            let name = if row.name.is_empty() {
+                String::from("(none)")
+            } else {
+                tax::describe(&row.name, row.rate)
+            };
+            let mut tax = app.format_price(row.tax, "");
+            if row.unconverted > 0 {
+                tax = format!("{} (+{}?)", tax, row.unconverted);
+            }
+            let style = if row.period.is_empty() {
+                Style::default().bold()
+            } else {
+                Style::default()
+            };
+            Row::new(vec![
+                Cell::from(period),
+                Cell::from(name),
+                amount(row.gross),
+                amount(row.gross - row.tax),
+                Cell::from(Line::from(tax).alignment(Alignment::Right)),
+            ])
+            .style(style)
+        })
+        .collect();
+
+    let widths = vec![
+        Constraint::Length(7),
+        Constraint::Min(0),
+        Constraint::Length(7 + 1 + 2 + 2),
+        Constraint::Length(7 + 1 + 2 + 2),
+        Constraint::Length(7 + 1 + 2 + 5),
+    ];
+
+    let mut title = String::from(" Tax ");
+    if !app.home_currency.is_empty() {
+        title = format!(" Tax in {} ", app.home_currency);
+    }
+    if let Some(query) = &app.filter.query {
+        title += &format!("of {} ", query.text);
+    }
+
+    let table = Table::new(body, widths)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(title)
+                .title_bottom(Line::from(" p: months or years ").right_aligned()),
+        )
+        .header(header)
+        .style(Style::default().fg(Color::White).bg(Color::Blue))
+        .row_highlight_style(Style::default().fg(Color::Black).bg(Color::Cyan));
+
+    frame.render_stateful_widget(table, layout, &mut app.view_state);
+}
+
+fn render_balances(frame: &mut Frame, layout: Rect, app: &mut App) {
+    let div = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(vec![Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(layout);
+
+    let header = Row::new(vec![
+        Cell::from(Line::from("Member")),
+        Cell::from(Line::from("Paid").alignment(Alignment::Center)),
+        Cell::from(Line::from("Share").alignment(Alignment::Center)),
+        Cell::from(Line::from("Sent").alignment(Alignment::Center)),
+        Cell::from(Line::from("Balance").alignment(Alignment::Center)),
+        Cell::from(Line::from("To settle up")),
+    ])
+    .style(Style::default().fg(Color::LightYellow));
+
+    let amount = |price: i64| Cell::from(Line::from(app.format_price(price, "")).right_aligned());
+
+    let body: Vec<Row> = app
+        .balances
+        .iter()
+        .map(|balance| {
+            let mut paid = app.format_price(balance.paid, "");
+            if balance.unconverted > 0 {
+                paid = format!("{} (+{}?)", paid, balance.unconverted);
+            }
+            let settle: Vec<String> = app
+                .settlement
+                .iter()
+                .filter(|transfer| transfer.from == balance.member)
+                .map(|transfer| {
+                    format!(
+                        "pays {} to {}",
+                        app.format_price(transfer.amount, ""),
+                        transfer.to
+                    )
+                })
+                .collect();
+            Row::new(vec![
+                Cell::from(balance.member.clone()),
+                Cell::from(Line::from(paid).alignment(Alignment::Right)),
+                amount(balance.share),
+                amount(balance.transfers),
+                amount(balance.balance()),
+                Cell::from(settle.join(", ")),
+            ])
+        })
+        .collect();
+
+    let widths = vec![
+        Constraint::Percentage(20),
+        Constraint::Length(7 + 1 + 2 + 5),
+        Constraint::Length(7 + 1 + 2),
+        Constraint::Length(7 + 1 + 2),
+        Constraint::Length(7 + 1 + 2),
+        Constraint::Min(0),
+    ];
+
+    let mut title = String::from(" Balances ");
+    if !app.home_currency.is_empty() {
+        title = format!(" Balances in {} ", app.home_currency);
+    }
+
+    let table = Table::new(body, widths)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .header(header)
+        .style(Style::default().fg(Color::White).bg(Color::Blue));
+
+    frame.render_widget(table, div[0]);
+
+    let header = Row::new(vec![
+        Cell::from(Line::from("Date").alignment(Alignment::Center)),
+        Cell::from(Line::from("From")),
+        Cell::from(Line::from("To")),
+        Cell::from(Line::from("Amount").alignment(Alignment::Center)),
+    ])
+    .style(Style::default().fg(Color::LightYellow));
+
+    let body: Vec<Row> = app
+        .transfers
+        .iter()
+        .map(|transfer| {
+            Row::new(vec![
+                Cell::from(transfer.date.clone()),
+                Cell::from(transfer.from.clone()),
+                Cell::from(transfer.to.clone()),
+                amount(transfer.amount),
+            ])
+        })
+        .collect();
+
+    let widths = vec![
+        Constraint::Length(4 + 1 + 2 + 1 + 2),
+        Constraint::Percentage(40),
+        Constraint::Percentage(40),
+        Constraint::Length(7 + 1 + 2),
+    ];
+
+    let table = Table::new(body, widths)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(" Transfers ")
+                .title_bottom(Line::from(" s: settle up  F8: delete transfer ").right_aligned()),
+        )
+        .header(header)
+        .style(Style::default().fg(Color::White).bg(Color::Blue))
+        .row_highlight_style(Style::default().fg(Color::Black).bg(Color::Cyan));
+
+    frame.render_stateful_widget(table, div[1], &mut app.view_state);
+}
+
+fn render_trash(frame: &mut Frame, layout: Rect, app: &mut App) {
+    let header = Row::new(vec![
+        Cell::from(Line::from("Id").alignment(Alignment::Center)),
+        Cell::from(Line::from("Deleted").alignment(Alignment::Center)),
+        Cell::from(Line::from("Date").alignment(Alignment::Center)),
+        Cell::from(Line::from("Category")),
+        Cell::from(Line::from("Description")),
+        Cell::from(Line::from("Price").alignment(Alignment::Center)),
     ])
+    .style(Style::default().fg(Color::LightYellow));
+
+    let body: Vec<Row> = app
+        .trash
+        .iter()
+        .map(|item| {
+            let price = app.format_money(item.price, &item.currency);
+            Row::new(vec![
+                Cell::from(Line::from(item.id.to_string()).alignment(Alignment::Right)),
+                Cell::from(item.deleted_at.clone().unwrap_or_default()),
+                Cell::from(item.date.clone()),
+                Cell::from(item.category.clone()),
+                Cell::from(item.description.clone()),
+                Cell::from(Line::from(price).alignment(Alignment::Right)),
+            ])
+        })
+        .collect();
+
+    let widths = vec![
+        Constraint::Length(6),                         // id
+        Constraint::Length(4 + 1 + 2 + 1 + 2 + 1 + 8), // deleted
+        Constraint::Length(4 + 1 + 2 + 1 + 2),         // date
+        Constraint::Percentage(30),                    // category
+        Constraint::Min(0),                            // description
+        Constraint::Length(amount_width(
+            5 + 1 + 2 + 1 + 3,
+            app.trash
+                .iter()
+                .map(|item| app.format_money(item.price, &item.currency)),
+        )), // price, currency
+    ];
+
+    let table = Table::new(body, widths)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(" Trash ")
+                .title_bottom(
+                    Line::from(" r: restore  F8: purge  e: empty older than… ").right_aligned(),
+                ),
+        )
+        .header(header)
+        .style(Style::default().fg(Color::White).bg(Color::Blue))
+        .row_highlight_style(Style::default().fg(Color::Black).bg(Color::Cyan));
+
+    frame.render_stateful_widget(table, layout, &mut app.view_state);
 }
 
 fn render_text_area(frame: &mut Frame, layout: Rect, app: &mut App) {
     let prompt = Span::from(match app.state {
-        AppState::Browse => "> ",
-        AppState::InsertDate => "date> ",
+        AppState::Browse
+        | AppState::Stores
+        | AppState::Categories
+        | AppState::Trash
+        | AppState::TaxReport
+        | AppState::Balances
+        | AppState::Search => "> ",
+        AppState::EmptyTrash => "older than days> ",
+        AppState::PurgeItem => "purge> ",
+        AppState::ImportRates => "rates file> ",
+        AppState::ReceiptDate | AppState::InsertDate => "date> ",
+        AppState::ReceiptStore => "shop> ",
+        AppState::ReceiptTotal => "total> ",
         AppState::InsertDescription => "desc> ",
         AppState::InsertCategory => "cat…> ",
+        AppState::InsertStore => "shop> ",
+        AppState::RenameCategory => "rename to> ",
+        AppState::MergeCategories => "merge into> ",
+        AppState::EditTags => "tags> ",
+        AppState::FilterTags => "filter tags> ",
+        AppState::Filter => "filter> ",
+        AppState::SaveFilter => "save filter as> ",
+        AppState::TaxRate | AppState::CategoryTax => "tax rate> ",
+        AppState::SplitItem => "split> ",
+        AppState::Payer => "paid by> ",
+        AppState::Shares => "shares> ",
+        AppState::SettleUp => "settle up on> ",
+        AppState::InsertQuantity => "qty> ",
         AppState::InsertPrice => "cost> ",
     });
 
+    // Reason the filter, split or shares being typed are invalid, shown
+    // beside them.
+    let error = match app.state {
+        AppState::Filter if !app.get_text().is_empty() => filter::parse(app, app.get_text()).err(),
+        AppState::SplitItem => app.table_state.selected().and_then(|i| {
+            split::parse(
+                app,
+                app.get_text(),
+                app.items[i].price,
+                &app.items[i].currency,
+            )
+            .err()
+        }),
+        AppState::Shares => app.table_state.selected().and_then(|i| {
+            household::parse(
+                app,
+                app.get_text(),
+                app.items[i].price,
+                &app.items[i].currency,
+            )
+            .err()
+        }),
+        _ => None,
+    };
+
+    // Date or amount that what is being typed works out to, shown beside
+    // it, e.g. "Sun 2026-10-18" for "today" or "= 3.87" for "3x1.29".
+    let date = match app.state {
+        AppState::ReceiptDate | AppState::InsertDate | AppState::SettleUp => {
+            util::parse_date_input(app.get_text(), &app.date_format)
+        }
+        _ => None,
+    };
+    let money = match app.state {
+        AppState::ReceiptTotal | AppState::InsertPrice => app.parse_money(app.get_text()),
+        _ => None,
+    };
+    let preview = match (date, &money) {
+        (Some(date), _) => Some(date.format("%a %F").to_string()),
+        (None, Some((price, currency))) => {
+            Some(format!("= {}", app.format_money(*price, currency)))
+        }
+        (None, None) if app.state == AppState::TaxRate => tax_preview(app),
+        (None, None) if app.state == AppState::Shares => shares_preview(app),
+        (None, None) => None,
+    };
+
     let is_valid = match app.state {
-        AppState::InsertDate => util::parse_date(app.get_text()).is_some(),
-        AppState::InsertPrice => util::parse_price(app.get_text()).is_some(),
+        AppState::ReceiptDate | AppState::InsertDate | AppState::SettleUp => date.is_some(),
+        AppState::ReceiptTotal => money.is_some(),
+        AppState::EmptyTrash => app.get_text().parse::<u32>().is_ok(),
+        AppState::PurgeItem => app.get_text() == "y",
+        AppState::Search => !app.search_error,
+        AppState::Filter | AppState::SplitItem | AppState::Shares => error.is_none(),
+        AppState::SaveFilter => !app
+            .get_text()
+            .contains(|c: char| c.is_whitespace() || c == '@' || c == '"'),
+        AppState::InsertQuantity => {
+            app.get_text().is_empty()
+                || quantity::parse_quantity(&app.locale, app.get_text()).is_some()
+        }
+        AppState::InsertPrice => money.is_some(),
+        AppState::TaxRate | AppState::CategoryTax => {
+            app.get_text().is_empty() || app.tax_rates.rates.contains_key(app.get_text())
+        }
         _ => true,
     };
 
@@ -108,16 +891,95 @@ fn render_text_area(frame: &mut Frame, layout: Rect, app: &mut App) {
             .set_style(Style::default().fg(Color::Red).bold())
     }
 
+    let note = match (error, preview) {
+        (Some(e), _) => Span::styled(
+            format!(" {} ", e),
+            Style::default().fg(Color::Black).bg(Color::LightRed),
+        ),
+        (None, Some(preview)) => Span::styled(
+            format!(" {} ", preview),
+            Style::default().fg(Color::Black).bg(Color::LightGreen),
+        ),
+        (None, None) => Span::from(""),
+    };
+
     let div = Layout::default()
         .direction(Direction::Horizontal)
         .constraints(vec![
             Constraint::Length(prompt.width() as u16),
             Constraint::Min(0),
+            Constraint::Length(note.width() as u16),
         ])
         .split(layout);
 
     frame.render_widget(Paragraph::new(prompt), div[0]);
-    frame.render_widget(&app.textarea, div[1]);
+    if let Some(busy) = &app.busy {
+        frame.render_widget(Paragraph::new(busy_line(busy)), div[1]);
+    } else if matches!(
+        app.state,
+        AppState::Browse | AppState::Trash | AppState::Balances
+    ) && !app.message.is_empty()
+    {
+        let style = if app.message_is_error {
+            Style::default().fg(Color::Black).bg(Color::LightRed)
+        } else {
+            Style::default().fg(Color::Black).bg(Color::LightGreen)
+        };
+        frame.render_widget(Paragraph::new(app.message.as_str()).style(style), div[1]);
+    } else {
+        frame.render_widget(&app.textarea, div[1]);
+    }
+    frame.render_widget(Paragraph::new(note), div[2]);
+}
+
+// Tax and net amount of the selected item at the rate being typed, or
+// that of its category if none, e.g. "tax 0.48, net 2.51 at 19%".
+fn tax_preview(app: &App) -> Option<String> {
+    let mut item = app.items.get(app.table_state.selected()?)?.clone();
+    item.tax_rate = Some(String::from(app.get_text())).filter(|name| !name.is_empty());
+    let (_, rate) = app.tax_rates.of_item(&item, &app.category_separator)?;
+
+    let tax = tax::included(item.price, rate);
+    Some(format!(
+        "tax {}, net {} at {}",
+        app.format_price(tax, &item.currency),
+        app.format_price(item.price - tax, &item.currency),
+        tax::format_rate(rate)
+    ))
+}
+
+// Amount of each share of the selected item, e.g. "Ann 12.50, Bob 12.50".
+fn shares_preview(app: &App) -> Option<String> {
+    let item = app.items.get(app.table_state.selected()?)?;
+    let shares = household::parse(app, app.get_text(), item.price, &item.currency).ok()?;
+    let amounts = household::resolve(item.price, &shares).ok()?;
+
+    let parts: Vec<String> = shares
+        .iter()
+        .zip(amounts)
+        .map(|(share, amount)| {
+            format!(
+                "{} {}",
+                share.member,
+                app.format_price(amount, &item.currency)
+            )
+        })
+        .collect();
+    (!parts.is_empty()).then(|| parts.join(", "))
+}
+
+// Spinner and progress of the job the worker is running.
+fn busy_line(busy: &Busy) -> Line<'static> {
+    const SPINNER: [&str; 4] = ["|", "/", "-", "\\"];
+    let frame = (busy.started.elapsed().as_millis() / 100) as usize % SPINNER.len();
+
+    let mut text = format!("{} {}", SPINNER[frame], busy.job.describe());
+    if let Some(percent) = (busy.done * 100).checked_div(busy.total) {
+        text += &format!(" {}% ({} of {})", percent, busy.done, busy.total);
+    }
+    text += ", press Esc to cancel";
+
+    Line::from(text).style(Style::default().fg(Color::Black).bg(Color::LightYellow))
 }
 
 fn render_key_bar(frame: &mut Frame, layout: Rect) {
@@ -1,39 +1,199 @@
 use chrono::naive::NaiveDate;
+use chrono::{Datelike, Days, Weekday};
 
 pub fn today() -> String {
     chrono::offset::Local::now().format("%F").to_string()
 }
 
 pub fn parse_date(line: &str) -> Option<NaiveDate> {
-    if let Ok(date) = NaiveDate::parse_from_str(line, "%F") {
-        Some(date)
-    } else {
-        None
+    NaiveDate::parse_from_str(line, "%F").ok()
+}
+
+// Date as typed when entering items: "2026-10-18", "today", "yesterday",
+// "-3" for three days ago, "mon" for the last Monday, "18" for that day of
+// this month, "10-18" for that day of this year, or `local_format` if set,
+// e.g. "%d.%m.%Y" for "18.10.2026", which also reads "18.10" and "18-10"
+// day first.
+pub fn parse_date_input(line: &str, local_format: &str) -> Option<NaiveDate> {
+    parse_date_on(
+        line,
+        local_format,
+        chrono::offset::Local::now().date_naive(),
+    )
+}
+
+fn parse_date_on(line: &str, local_format: &str, today: NaiveDate) -> Option<NaiveDate> {
+    let line = line.trim();
+
+    if let Some(date) = parse_date(line) {
+        return Some(date);
+    }
+    if !local_format.is_empty() {
+        if let Ok(date) = NaiveDate::parse_from_str(line, local_format) {
+            return Some(date);
+        }
+    }
+
+    match line.to_lowercase().as_str() {
+        "today" => return Some(today),
+        "yesterday" => return today.pred_opt(),
+        _ => (),
+    }
+
+    if let Some(days) = line.strip_prefix('-') {
+        let days: u64 = days.parse().ok()?;
+        return today.checked_sub_days(Days::new(days));
+    }
+
+    // The most recent such day, which may be today
+    if let Ok(weekday) = line.parse::<Weekday>() {
+        let days =
+            (today.weekday().num_days_from_monday() + 7 - weekday.num_days_from_monday()) % 7;
+        return today.checked_sub_days(Days::new(days.into()));
+    }
+
+    // Day and month in the order of the local format, separated as there
+    // or by "-"
+    let separator = local_format
+        .chars()
+        .find(|&c| !c.is_ascii_alphanumeric() && c != '%')
+        .unwrap_or('-');
+    let day_first = matches!(
+        (local_format.find("%d"), local_format.find("%m")),
+        (Some(d), Some(m)) if d < m
+    );
+    let parts: Vec<&str> = line
+        .trim_end_matches(separator)
+        .split(['-', separator])
+        .collect();
+    let (month, day) = match parts[..] {
+        [day] if day.len() <= 2 => return today.with_day(day.parse().ok()?),
+        [day, month] if day_first => (month, day),
+        [month, day] => (month, day),
+        _ => return None,
+    };
+    if month.len() > 2 || day.len() > 2 {
+        return None;
+    }
+    NaiveDate::from_ymd_opt(today.year(), month.parse().ok()?, day.parse().ok()?)
+}
+
+// Whether a strftime format, e.g. "%d.%m.%Y", writes dates that it can
+// read back.
+pub fn is_date_format(format: &str) -> bool {
+    use std::fmt::Write;
+
+    let sample = NaiveDate::from_ymd_opt(2026, 10, 18).unwrap_or_default();
+    let mut text = String::new();
+    write!(text, "{}", sample.format(format)).is_ok()
+        && NaiveDate::parse_from_str(&text, format) == Ok(sample)
+}
+
+pub fn is_tag_separator(c: char) -> bool {
+    c.is_whitespace() || c == ',' || c == '+'
+}
+
+// Distinct tags in alphabetical order.
+pub fn split_tags(line: &str) -> Vec<String> {
+    let mut tags: Vec<String> = line
+        .split(is_tag_separator)
+        .filter(|tag| !tag.is_empty())
+        .map(String::from)
+        .collect();
+
+    tags.sort();
+    tags.dedup();
+    tags
+}
+
+// Words to highlight for a full-text query, e.g. "oli*" gives "oli" and
+// "\"olive oil\" NOT spray" gives "olive" and "oil".
+pub fn search_terms(query: &str) -> Vec<String> {
+    let mut terms = Vec::new();
+    let mut negated = false;
+
+    for word in query.split(|c: char| c.is_whitespace() || "\"()+^".contains(c)) {
+        // Drop column filters, e.g. "category:food"
+        let word = word.rsplit(':').next().unwrap_or(word);
+        let word = word.trim_end_matches('*');
+
+        match word {
+            "" | "AND" | "OR" | "NEAR" => (),
+            "NOT" => {
+                negated = true;
+                continue;
+            }
+            _ if negated => (),
+            _ => terms.push(String::from(word)),
+        }
+        negated = false;
     }
+
+    terms
 }
 
-pub fn format_price(price: i64) -> String {
-    format!("{}.{:02}", price / 100, price % 100)
+pub fn starts_with_ignore_case(text: &str, prefix: &str) -> bool {
+    text.len() >= prefix.len()
+        && text.is_char_boundary(prefix.len())
+        && text[..prefix.len()].to_lowercase() == prefix.to_lowercase()
 }
 
-pub fn parse_price(line: &str) -> Option<i64> {
-    let dollars: &str;
-    let cents: Option<i64>;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(text: &str) -> Option<NaiveDate> {
+        parse_date(text)
+    }
+
+    #[test]
+    fn relative_dates() {
+        // A Sunday
+        let today = date("2026-10-18").unwrap();
+        let parse = |line| parse_date_on(line, "", today);
+
+        assert_eq!(parse("2026-09-01"), date("2026-09-01"));
+        assert_eq!(parse(" today "), Some(today));
+        assert_eq!(parse("Today"), Some(today));
+        assert_eq!(parse("yesterday"), date("2026-10-17"));
+        assert_eq!(parse("-3"), date("2026-10-15"));
+        assert_eq!(parse("-0"), Some(today));
+        assert_eq!(parse("-x"), None);
+        assert_eq!(parse("-99999999999999"), None);
 
-    if let Some((d, c)) = line.split_once('.') {
-        dollars = d;
-        cents = match c.len() {
-            0 => Some(0i64),
-            1 => c.parse::<i64>().ok().map(|c| c * 10),
-            2 => c.parse::<i64>().ok(),
-            _ => None,
-        };
-    } else {
-        dollars = line;
-        cents = Some(0i64)
+        assert_eq!(parse("sun"), Some(today));
+        assert_eq!(parse("mon"), date("2026-10-12"));
+        assert_eq!(parse("Saturday"), date("2026-10-17"));
+        assert_eq!(parse("someday"), None);
     }
 
-    cents
-        .zip(dollars.parse::<i64>().ok())
-        .map(|(c, d)| 100 * d + c)
+    #[test]
+    fn partial_dates() {
+        let today = date("2026-10-18").unwrap();
+
+        for format in ["", "%F", "%Y/%m/%d"] {
+            let parse = |line| parse_date_on(line, format, today);
+            assert_eq!(parse("5"), date("2026-10-05"), "{}", format);
+            assert_eq!(parse("09-30"), date("2026-09-30"), "{}", format);
+            assert_eq!(parse("9-5"), date("2026-09-05"), "{}", format);
+            assert_eq!(parse("32"), None, "{}", format);
+            assert_eq!(parse("30-09"), None, "{}", format);
+            assert_eq!(parse("2026-10"), None, "{}", format);
+        }
+
+        let german = |line| parse_date_on(line, "%d.%m.%Y", today);
+        assert_eq!(german("18.10.2026"), Some(today));
+        assert_eq!(german("5"), date("2026-10-05"));
+        assert_eq!(german("30.9."), date("2026-09-30"));
+        assert_eq!(german("30.9"), date("2026-09-30"));
+        assert_eq!(german("30-9"), date("2026-09-30"));
+        assert_eq!(german("9.30"), None);
+
+        let us = |line| parse_date_on(line, "%m/%d/%Y", today);
+        assert_eq!(us("10/18/2026"), Some(today));
+        assert_eq!(us("9/30"), date("2026-09-30"));
+        assert_eq!(us("9-30"), date("2026-09-30"));
+        assert_eq!(us("30/9"), None);
+        assert_eq!(us("yesterday"), date("2026-10-17"));
+    }
 }
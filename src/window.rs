@@ -0,0 +1,382 @@
+use crate::app::{App, DbItem, ItemFilter, SortOrder};
+use crate::db;
+use rusqlite::{Connection, Result};
+
+// The main table only holds a window of the items matching the filter, in
+// sort order, around the cursor.  Moving the cursor fetches more rows next
+// to the window by keyset pagination, and drops rows far behind it, so the
+// cost of browsing does not grow with the size of the database.
+//
+// `app.items` is the window, `app.table_state` selects within it, and
+// `app.items_offset` is the position of its first row among all items.
+
+// Rows fetched per query.  The window keeps at least this many rows on
+// each side of the cursor, more than fit on any screen.
+const PAGE: usize = 100;
+
+// Rows kept on each side of the cursor when trimming the window.
+const KEEP: usize = 2 * PAGE;
+
+// What reload reads: the item count and totals, and the window around the
+// item with the given id, or from the top if it no longer matches the
+// filter.  It does not need App, so that the worker can read it.
+#[derive(Clone, Debug)]
+pub struct Window {
+    count: usize,
+
+    // Amounts per day and currency, see db::select_totals.
+    totals: Vec<(String, String, i64, usize)>,
+
+    items: Vec<DbItem>,
+    offset: usize,
+    selected: Option<usize>,
+}
+
+pub fn reload(app: &mut App, conn: &Connection, id: Option<i64>) -> Result<()> {
+    let window = read(conn, app.sort, &app.filter, id)?;
+    show(app, window);
+
+    Ok(())
+}
+
+pub fn read(
+    conn: &Connection,
+    sort: SortOrder,
+    filter: &ItemFilter,
+    id: Option<i64>,
+) -> Result<Window> {
+    let count = db::count_items(conn, sort, filter, None)?;
+    let totals = db::select_totals(conn, filter)?;
+
+    let item = match id {
+        Some(id) => db::select_filtered_item(conn, filter, id)?,
+        None => None,
+    };
+    let (items, offset, selected) = match item {
+        Some(item) => {
+            let (items, offset, selected) = around(conn, sort, filter, item)?;
+            (items, offset, Some(selected))
+        }
+        None => {
+            let items = db::select_page(conn, sort, filter, None, true, KEEP)?;
+            let selected = if items.is_empty() { None } else { Some(0) };
+            (items, 0, selected)
+        }
+    };
+
+    Ok(Window {
+        count,
+        totals,
+        items,
+        offset,
+        selected,
+    })
+}
+
+// Replace the window, count and totals with those read.
+pub fn show(app: &mut App, window: Window) {
+    app.item_count = window.count;
+    app.total = 0;
+    app.unconverted = 0;
+    for (date, currency, amount, count) in window.totals {
+        match app.home_amount(amount, &currency, &date) {
+            Some(amount) => app.total += amount,
+            None => app.unconverted += count,
+        }
+    }
+
+    app.items = window.items;
+    app.items_offset = window.offset;
+    app.table_scroll = window.selected.unwrap_or(0);
+    app.table_state.select(window.selected);
+}
+
+// Select the item with the given id, loading the window around it if it is
+// not already loaded.
+pub fn select(app: &mut App, conn: &Connection, id: Option<i64>) -> Result<()> {
+    let i = id.and_then(|id| app.items.iter().position(|item| item.id == id));
+
+    match i {
+        Some(i) => {
+            app.table_state.select(Some(i));
+            fill(app, conn)
+        }
+        None => load(app, conn, id),
+    }
+}
+
+// Load the window around the item with the given id and select it, or the
+// first item if it does not match the filter.
+pub fn load(app: &mut App, conn: &Connection, id: Option<i64>) -> Result<()> {
+    let item = match id {
+        Some(id) => db::select_filtered_item(conn, &app.filter, id)?,
+        None => None,
+    };
+    let item = match item {
+        Some(item) => item,
+        None => return home(app, conn),
+    };
+
+    let (items, offset, selected) = around(conn, app.sort, &app.filter, item)?;
+    app.items = items;
+    app.items_offset = offset;
+    app.table_state.select(Some(selected));
+    app.table_scroll = selected;
+
+    Ok(())
+}
+
+// Rows on each side of an item, with the position of the first among all
+// items, and of the item in the rows.
+fn around(
+    conn: &Connection,
+    sort: SortOrder,
+    filter: &ItemFilter,
+    item: DbItem,
+) -> Result<(Vec<DbItem>, usize, usize)> {
+    let mut items = db::select_page(conn, sort, filter, Some(&item), false, PAGE)?;
+    let after = db::select_page(conn, sort, filter, Some(&item), true, PAGE)?;
+
+    let selected = items.len();
+    let offset = db::count_items(conn, sort, filter, Some(&item))? - selected;
+    items.push(item);
+    items.extend(after);
+
+    Ok((items, offset, selected))
+}
+
+pub fn home(app: &mut App, conn: &Connection) -> Result<()> {
+    app.items = db::select_page(conn, app.sort, &app.filter, None, true, KEEP)?;
+    app.items_offset = 0;
+    app.table_scroll = 0;
+    app.table_state
+        .select(if app.items.is_empty() { None } else { Some(0) });
+
+    Ok(())
+}
+
+pub fn end(app: &mut App, conn: &Connection) -> Result<()> {
+    app.items = db::select_page(conn, app.sort, &app.filter, None, false, KEEP)?;
+    app.items_offset = app.item_count.saturating_sub(app.items.len());
+    app.table_scroll = app.items.len();
+    app.table_state.select(app.items.len().checked_sub(1));
+
+    Ok(())
+}
+
+// Take out an item that matched the filter before a change.
+pub fn remove(app: &mut App, item: &DbItem) {
+    app.item_count = app.item_count.saturating_sub(1);
+
+    let i = match app.items.iter().position(|row| row.id == item.id) {
+        Some(i) => i,
+        None => {
+            // Outside the window, which moves up if the item was before it
+            let first = app.items.first();
+            if first.is_some_and(|first| app.sort.compare(item, first).is_lt()) {
+                app.items_offset = app.items_offset.saturating_sub(1);
+            }
+            return;
+        }
+    };
+
+    app.items.remove(i);
+    if i < app.table_scroll {
+        app.table_scroll -= 1;
+    }
+
+    // The cursor stays on the same item, or moves to the next one if it
+    // was on the item taken out
+    let last = app.items.len().checked_sub(1);
+    let cursor = app
+        .table_state
+        .selected()
+        .map(|cursor| if cursor > i { cursor - 1 } else { cursor });
+    app.table_state
+        .select(cursor.zip(last).map(|(cursor, last)| cursor.min(last)));
+}
+
+// Put in an item that matches the filter after a change, in its place in
+// sort order if that is inside the window.
+pub fn insert(app: &mut App, item: DbItem) {
+    let at_start = app.items_offset == 0;
+    let at_end = app.items_offset + app.items.len() >= app.item_count;
+    app.item_count += 1;
+
+    let i = app
+        .items
+        .partition_point(|row| app.sort.compare(row, &item).is_lt());
+    if i == 0 && !at_start {
+        app.items_offset += 1;
+        return;
+    }
+    if i == app.items.len() && !at_end {
+        return;
+    }
+
+    app.items.insert(i, item);
+    if i < app.table_scroll {
+        app.table_scroll += 1;
+    }
+
+    let cursor = match app.table_state.selected() {
+        Some(cursor) if cursor >= i => cursor + 1,
+        Some(cursor) => cursor,
+        None => i,
+    };
+    app.table_state.select(Some(cursor));
+}
+
+// Move the cursor by up to PAGE rows, clamped to the first and last item.
+pub fn move_by(app: &mut App, conn: &Connection, delta: isize) -> Result<()> {
+    let cursor = match app.table_state.selected() {
+        Some(cursor) => cursor,
+        None => return Ok(()),
+    };
+
+    let last = app.items.len().saturating_sub(1);
+    let cursor = cursor.saturating_add_signed(delta).min(last);
+    app.table_state.select(Some(cursor));
+
+    fill(app, conn)
+}
+
+// Fetch rows so that PAGE rows are loaded on each side of the cursor, where
+// there are any, and drop rows more than KEEP away from it.
+fn fill(app: &mut App, conn: &Connection) -> Result<()> {
+    let mut cursor = match app.table_state.selected() {
+        Some(cursor) => cursor,
+        None => return Ok(()),
+    };
+
+    if app.items.len() - cursor <= PAGE {
+        let more = db::select_page(conn, app.sort, &app.filter, app.items.last(), true, PAGE)?;
+        app.items.extend(more);
+    }
+
+    if cursor < PAGE && app.items_offset > 0 {
+        let more = db::select_page(conn, app.sort, &app.filter, app.items.first(), false, PAGE)?;
+        let n = more.len();
+        app.items.splice(0..0, more);
+        app.items_offset = app.items_offset.saturating_sub(n);
+        app.table_scroll += n;
+        cursor += n;
+    }
+
+    let front = cursor.saturating_sub(KEEP);
+    app.items.drain(..front);
+    app.items.truncate(cursor - front + KEEP + 1);
+    app.items_offset += front;
+    app.table_scroll = app.table_scroll.saturating_sub(front);
+    app.table_state.select(Some(cursor - front));
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::SortColumn;
+
+    // A database of n items, dated and priced so that sort keys repeat,
+    // and the ids of all items in the app's sort order.
+    fn setup(app: &App, n: usize) -> (Connection, Vec<i64>) {
+        let conn = db::open(std::path::Path::new(":memory:")).unwrap();
+        for i in 0..n {
+            let mut item = app.new_item.clone();
+            item.date = format!("2026-10-{:02}", 1 + i % 28);
+            item.description = format!("Item {}", i);
+            item.price = (i % 7) as i64 * 100;
+            db::insert_item(&conn, &item).unwrap();
+        }
+
+        let ids = db::select_page(&conn, app.sort, &app.filter, None, true, n + 1)
+            .unwrap()
+            .iter()
+            .map(|item| item.id)
+            .collect();
+        (conn, ids)
+    }
+
+    // The window is a run of consecutive items in sort order, with PAGE
+    // rows on each side of the cursor where there are any, and no more
+    // than KEEP.
+    fn check(app: &App, ids: &[i64]) {
+        let cursor = app.table_state.selected().unwrap();
+        let window: Vec<i64> = app.items.iter().map(|item| item.id).collect();
+        let offset = app.items_offset;
+        assert_eq!(window, ids[offset..offset + window.len()]);
+
+        let before = cursor;
+        let after = window.len() - cursor - 1;
+        assert!(before <= KEEP && after <= KEEP);
+        assert!(before >= PAGE || offset == 0, "{} before", before);
+        assert!(after >= PAGE || offset + window.len() == ids.len());
+    }
+
+    fn selected(app: &App) -> i64 {
+        app.items[app.table_state.selected().unwrap()].id
+    }
+
+    #[test]
+    fn paging() {
+        for (column, descending) in [(SortColumn::Date, false), (SortColumn::Price, true)] {
+            let mut app = App::new();
+            app.sort.column = column;
+            app.sort.descending = descending;
+            let (conn, ids) = setup(&app, 5 * PAGE + 17);
+
+            reload(&mut app, &conn, None).unwrap();
+            assert_eq!(app.item_count, ids.len());
+            check(&app, &ids);
+
+            // Down past several pages one screen at a time, then back up
+            let mut position = 0;
+            while position + 1 < ids.len() {
+                move_by(&mut app, &conn, 37).unwrap();
+                position = (position + 37).min(ids.len() - 1);
+                check(&app, &ids);
+                assert_eq!(selected(&app), ids[position]);
+            }
+            while position > 0 {
+                move_by(&mut app, &conn, -(PAGE as isize)).unwrap();
+                position = position.saturating_sub(PAGE);
+                check(&app, &ids);
+                assert_eq!(selected(&app), ids[position]);
+            }
+
+            end(&mut app, &conn).unwrap();
+            assert_eq!(selected(&app), ids[ids.len() - 1]);
+            check(&app, &ids);
+
+            // An item in the middle, outside the window, and one inside it
+            select(&mut app, &conn, Some(ids[250])).unwrap();
+            assert_eq!(selected(&app), ids[250]);
+            check(&app, &ids);
+            select(&mut app, &conn, Some(ids[260])).unwrap();
+            assert_eq!(selected(&app), ids[260]);
+            check(&app, &ids);
+
+            home(&mut app, &conn).unwrap();
+            assert_eq!(selected(&app), ids[0]);
+            check(&app, &ids);
+        }
+    }
+
+    #[test]
+    fn reload_keeps_item() {
+        let mut app = App::new();
+        let (conn, ids) = setup(&app, 3 * PAGE);
+
+        reload(&mut app, &conn, Some(ids[150])).unwrap();
+        assert_eq!(selected(&app), ids[150]);
+        assert_eq!(app.items_offset, 150 - PAGE);
+        check(&app, &ids);
+
+        // An item that no longer matches goes back to the top
+        db::delete_item(&conn, ids[150]).unwrap();
+        reload(&mut app, &conn, Some(ids[150])).unwrap();
+        assert_eq!(selected(&app), ids[0]);
+        assert_eq!(app.item_count, ids.len() - 1);
+    }
+}
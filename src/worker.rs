@@ -0,0 +1,286 @@
+use crate::app::{App, ItemFilter, SortOrder};
+use crate::household::{self, Balance, Transfer};
+use crate::model::{self, Snapshot};
+use crate::stats::{self, CategoryNode, Cheapest, StoreStats, TaxRow};
+use crate::{currency, db};
+use rusqlite::{Connection, InterruptHandle};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::time::Instant;
+
+// Operations that may take long on a large database run on a thread of
+// their own, with a second connection, so that the screen keeps updating.
+// These are the ones that read or change every item, rather than a window
+// of them or a single one.  The event loop hands jobs over and receives
+// progress, and what was read, over channels, and leaves the database
+// alone until the job is finished.  Changes made by the worker are then
+// picked up through PRAGMA data_version, like changes made by any other
+// program.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Job {
+    // Optimise the full-text index and VACUUM.
+    Compact,
+
+    // Import exchange rates from a CSV file.
+    ImportRates(PathBuf),
+
+    // Purge items deleted at least this many days ago.
+    EmptyTrash(u32),
+
+    // Read everything again with a new filter, keeping the cursor on the
+    // item with the given id, see model::reload.
+    Reload {
+        sort: SortOrder,
+        filter: ItemFilter,
+        id: Option<i64>,
+    },
+
+    // Rename a category and its subcategories in every item, see
+    // db::rename_category, then work out the Categories view.
+    RenameCategory {
+        old: String,
+        new: String,
+        separator: String,
+    },
+
+    // Move all items of the given categories into one, then work out the
+    // Categories view.
+    MergeCategories {
+        categories: Vec<String>,
+        into: String,
+    },
+
+    // Work out a statistics view.
+    Scan(Scan),
+}
+
+// Statistics views, which are worked out from all items, or those that
+// match a filter, as they are read.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Scan {
+    Stores,
+    Categories,
+
+    // Items matching the filter, per year rather than month if set.
+    TaxReport(ItemFilter, bool),
+
+    Balances,
+}
+
+// A statistics view as worked out, see stats.rs and household.rs.
+pub enum Stats {
+    Stores(Vec<StoreStats>, Vec<Cheapest>),
+    Categories(Vec<CategoryNode>),
+    TaxReport(Vec<TaxRow>),
+    Balances(Vec<Balance>, Vec<Transfer>),
+}
+
+impl Job {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Job::Compact => "Compacting database",
+            Job::ImportRates(_) => "Importing exchange rates",
+            Job::EmptyTrash(_) => "Emptying trash",
+            Job::Reload { .. } => "Loading items",
+            Job::RenameCategory { .. } => "Renaming category",
+            Job::MergeCategories { .. } => "Merging categories",
+            Job::Scan(_) => "Working out statistics",
+        }
+    }
+}
+
+// What a finished job read for the event loop to show, if anything.
+pub enum Outcome {
+    Done,
+    Reloaded(Box<Snapshot>),
+
+    Scanned(Stats),
+}
+
+enum Event {
+    // Rows done so far, and the total.
+    Progress(usize, usize),
+
+    // Message to show, if any, whether it reports a problem, and what
+    // was read.
+    Finished(String, bool, Outcome),
+}
+
+// Job in progress, for the busy indicator.
+#[derive(Clone, Debug)]
+pub struct Busy {
+    pub job: Job,
+    pub done: usize,
+    pub total: usize,
+    pub started: Instant,
+}
+
+pub struct Worker {
+    jobs: Sender<Job>,
+    events: Receiver<Event>,
+    interrupt: InterruptHandle,
+    cancel: Arc<AtomicBool>,
+}
+
+impl Worker {
+    pub fn start(path: &Path) -> Result<Worker, Box<dyn std::error::Error>> {
+        let mut conn = db::open(path)?;
+        let interrupt = conn.get_interrupt_handle();
+        let cancel = Arc::new(AtomicBool::new(false));
+
+        let (jobs, job_rx) = mpsc::channel();
+        let (event_tx, events) = mpsc::channel();
+        let flag = Arc::clone(&cancel);
+
+        std::thread::spawn(move || {
+            for job in job_rx {
+                let (message, is_error, outcome) = run(&mut conn, &job, &flag, &event_tx);
+                if event_tx
+                    .send(Event::Finished(message, is_error, outcome))
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        });
+
+        Ok(Worker {
+            jobs,
+            events,
+            interrupt,
+            cancel,
+        })
+    }
+
+    pub fn submit(&self, job: Job) -> Busy {
+        self.cancel.store(false, Ordering::SeqCst);
+        // The thread only stops when the Worker is dropped
+        let _ = self.jobs.send(job.clone());
+
+        Busy {
+            job,
+            done: 0,
+            total: 0,
+            started: Instant::now(),
+        }
+    }
+
+    // Stop the running job between rows, or in the middle of a statement.
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::SeqCst);
+        self.interrupt.interrupt();
+    }
+
+    // Update the busy indicator with any progress, returning the message
+    // and what was read once the job has finished.
+    pub fn poll(&self, busy: &mut Busy) -> Option<(String, bool, Outcome)> {
+        while let Ok(event) = self.events.try_recv() {
+            match event {
+                Event::Progress(done, total) => {
+                    busy.done = done;
+                    busy.total = total;
+                }
+                Event::Finished(message, is_error, outcome) => {
+                    return Some((message, is_error, outcome))
+                }
+            }
+        }
+
+        None
+    }
+}
+
+fn run(
+    conn: &mut Connection,
+    job: &Job,
+    cancel: &AtomicBool,
+    events: &Sender<Event>,
+) -> (String, bool, Outcome) {
+    let mut progress = |done: usize, total: usize| {
+        // Report every hundred rows, which is often enough to watch
+        if done.is_multiple_of(100) {
+            let _ = events.send(Event::Progress(done, total));
+        }
+        !cancel.load(Ordering::SeqCst)
+    };
+
+    let done = |message: String| (message, Outcome::Done);
+
+    let result = match job {
+        Job::Compact => db::compact(conn).map(|_| done(String::from("Compacted database"))),
+        Job::ImportRates(path) => {
+            let rates = match currency::read_csv(path) {
+                Ok(rates) => rates,
+                Err(err) => {
+                    let message = format!("{} failed: {}", job.describe(), err);
+                    return (message, true, Outcome::Done);
+                }
+            };
+            db::insert_rates(conn, &rates, &mut progress)
+                .map(|_| done(format!("Imported {} exchange rates", rates.len())))
+        }
+        Job::EmptyTrash(days) => db::purge_items(conn, None, Some(*days), &mut progress)
+            .map(|n| done(format!("Purged {} items from trash", n))),
+        Job::Reload { sort, filter, id } => model::read(conn, *sort, filter, *id)
+            .map(|snapshot| (String::new(), Outcome::Reloaded(Box::new(snapshot)))),
+        Job::RenameCategory {
+            old,
+            new,
+            separator,
+        } => db::rename_category(conn, old, new, separator).and_then(|n| {
+            let message = format!("Renamed {} to {} in {} items", old, new, n);
+            scan(conn, &Scan::Categories).map(|outcome| (message, outcome))
+        }),
+        Job::MergeCategories { categories, into } => db::merge_categories(conn, categories, into)
+            .and_then(|n| {
+                let message = format!("Moved {} items to {}", n, into);
+                scan(conn, &Scan::Categories).map(|outcome| (message, outcome))
+            }),
+        Job::Scan(view) => scan(conn, view).map(|outcome| (String::new(), outcome)),
+    };
+
+    match result {
+        Ok((message, outcome)) => (message, false, outcome),
+        Err(err) if db::is_cancelled(&err) => {
+            (format!("{} cancelled", job.describe()), true, Outcome::Done)
+        }
+        Err(err) => (
+            format!("{} failed: {}", job.describe(), err),
+            true,
+            Outcome::Done,
+        ),
+    }
+}
+
+fn scan(conn: &Connection, view: &Scan) -> rusqlite::Result<Outcome> {
+    let mut app = App::new();
+    model::load_settings(&mut app, conn)?;
+    let all = ItemFilter::default();
+
+    let stats = match view {
+        Scan::Stores => Stats::Stores(
+            db::scan_items(conn, &all, |items| stats::store_stats(&app, items))?,
+            db::scan_items(conn, &all, |items| stats::cheapest(&app, items))?,
+        ),
+        Scan::Categories => Stats::Categories(db::scan_items(conn, &all, |items| {
+            stats::category_tree(&app, items, &app.category_separator)
+        })?),
+        Scan::TaxReport(filter, yearly) => {
+            Stats::TaxReport(db::scan_items(conn, filter, |items| {
+                stats::tax_report(&app, items, *yearly)
+            })?)
+        }
+        Scan::Balances => {
+            let members = db::select_members(conn)?;
+            let transfers = db::select_transfers(conn)?;
+            let balances = db::scan_items(conn, &all, |items| {
+                household::balances(&app, &members, items, &transfers)
+            })?;
+            Stats::Balances(balances, transfers)
+        }
+    };
+
+    Ok(Outcome::Scanned(stats))
+}